@@ -1,7 +1,20 @@
-use omage::Engine;
+use omage::{Engine, EngineMode, EngineOptions};
+use omage::application::Application;
+use omage_util::PathOptions;
+
+struct Sandbox;
+impl Application for Sandbox{}
 
 fn main(){
-    let engine = Engine::new("omage-bin");
-    engine.set_window_name(String::from("omage"));
-    engine.dispatch();
-}
\ No newline at end of file
+    let options = EngineOptions{
+        app_version : env!("CARGO_PKG_VERSION").to_owned(),
+        path_options : PathOptions::from_args(std::env::args_os().skip(1)),
+        ..EngineOptions::new(EngineMode::Windowed)
+    };
+    let engine = match Engine::with_options("omage-bin", options){
+        Ok(engine) => {engine}
+        Err(error) => {eprintln!("Failed to start omage, {}.", error); std::process::exit(1)}
+    };
+    if let Err(error) = engine.handle().set_window_name("omage").wait(){eprintln!("Failed to set the window name, {}.", error)}
+    if let Err(error) = engine.dispatch(Sandbox){eprintln!("omage stopped with an error, {}.", error); std::process::exit(1)}
+}
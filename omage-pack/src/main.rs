@@ -0,0 +1,45 @@
+#![allow(clippy::needless_return)]
+use std::path::PathBuf;
+use omage_util::archive::{Archive, ArchiveBuilder, Compression};
+
+const USAGE : &str = "usage: omage-pack <assets directory> <output archive> [--store] [--alignment <bytes>]";
+
+///Packs an assets directory into an archive the engine loads in place of the loose files.
+fn main(){
+    let mut arguments = std::env::args().skip(1);
+    let mut paths = vec![];
+    let mut compression = Compression::Deflate;
+    let mut alignment = 16;
+    while let Some(argument) = arguments.next(){
+        match argument.as_str(){
+            "--store" => {compression = Compression::Store}
+            "--alignment" => {
+                alignment = match arguments.next().and_then(|value| value.parse().ok()){
+                    Some(alignment) => {alignment}
+                    None => {eprintln!("{}", USAGE); std::process::exit(2)}
+                }
+            }
+            _ => {paths.push(PathBuf::from(argument))}
+        }
+    }
+    let (source, output) = match paths.as_slice(){
+        [source, output] => {(source, output)}
+        _ => {eprintln!("{}", USAGE); std::process::exit(2)}
+    };
+    let mut builder = ArchiveBuilder::new(alignment);
+    if let Err(error) = builder.add_directory(source, compression){eprintln!("Failed to read {:?}, {}.", source, error); std::process::exit(1)}
+    if let Err(error) = builder.write(output){eprintln!("Failed to write {:?}, {}.", output, error); std::process::exit(1)}
+    //Reading every entry back catches a broken archive before it ships.
+    let archive = match Archive::open(output){
+        Ok(archive) => {archive}
+        Err(error) => {eprintln!("Failed to open the written archive, {}.", error); std::process::exit(1)}
+    };
+    let mut size = 0;
+    let mut stored_size = 0;
+    for (path, entry) in archive.entries(){
+        if let Err(error) = archive.read(path){eprintln!("Failed to verify {}, {}.", path, error); std::process::exit(1)}
+        size += entry.size;
+        stored_size += entry.stored_size;
+    }
+    println!("Packed {} files, {} bytes into {} bytes.", builder.len(), size, stored_size);
+}
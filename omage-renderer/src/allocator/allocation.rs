@@ -1,5 +1,6 @@
-use ash::vk::{MemoryRequirements, MemoryPropertyFlags, Handle, Image, Buffer};
+use ash::vk::{MemoryRequirements, MemoryPropertyFlags, MemoryMapFlags, Handle, Image, Buffer};
 
+use crate::error::RenderError;
 use super::Allocator;
 
 pub struct Allocation{
@@ -7,46 +8,57 @@ pub struct Allocation{
     region : u64,
 }
 impl Allocator{
-    pub unsafe fn create_allocation(&mut self, memory_requirements : MemoryRequirements, flags : MemoryPropertyFlags) -> Allocation{
+    pub unsafe fn create_allocation(&mut self, memory_requirements : MemoryRequirements, flags : MemoryPropertyFlags) -> Result<Allocation, RenderError>{
         for i in 0..self.blocks.len(){
-            if self.memory_properties.memory_types[self.blocks[i].memory_type as usize].property_flags.contains(flags) && (memory_requirements.memory_type_bits & (1 << self.blocks[i].memory.as_raw() as u32)) > 0{
-                match self.try_fit_in_block(memory_requirements.size, self.blocks[i].memory.as_raw(), memory_requirements.alignment){
-                    Some(region) => {
-                        return Allocation{
-                            block : self.blocks[i].memory.as_raw(),
-                            region,
-                        }
-                    }
-                    None=>{}
-                };
+            if self.memory_properties.memory_types[self.blocks[i].memory_type as usize].property_flags.contains(flags) && (memory_requirements.memory_type_bits & (1 << self.blocks[i].memory_type)) > 0{
+                if let Some(region) = self.try_fit_in_block(memory_requirements.size, self.blocks[i].memory.as_raw(), memory_requirements.alignment){
+                    return Ok(Allocation{
+                        block : self.blocks[i].memory.as_raw(),
+                        region,
+                    })
+                }
             }
         }
-        let block = self.create_block(memory_requirements.size, memory_requirements.memory_type_bits, flags);
-        let region = self.try_fit_in_block(memory_requirements.size, block, memory_requirements.alignment).unwrap();
-        return Allocation{
+        let block = self.create_block(memory_requirements.size, memory_requirements.memory_type_bits, flags)?;
+        let region = self.try_fit_in_block(memory_requirements.size, block, memory_requirements.alignment).ok_or(RenderError::OutOfMemory)?;
+        return Ok(Allocation{
             block,region,
-        }
+        })
     }
     pub unsafe fn destroy_allocation(&mut self, allocation : &Allocation){
         let block_id = self.blocks.iter().position(|block| block.memory.as_raw() == allocation.block).unwrap();
         let index = self.blocks[block_id].regions.iter().position(|region| region.offset == allocation.region).unwrap();
         self.blocks[block_id].regions.remove(index);
-        if self.blocks[block_id].regions.len() == 0{
+        if self.blocks[block_id].regions.is_empty(){
             self.destroy_block(allocation.block);
         }
     }
-    pub unsafe fn allocate_image_memory(&mut self, image : Image, flags : MemoryPropertyFlags) -> Allocation{
+    pub unsafe fn allocate_image_memory(&mut self, image : Image, flags : MemoryPropertyFlags) -> Result<Allocation, RenderError>{
         let memory_requirements = self.device.get_image_memory_requirements(image);
-        let allocation = self.create_allocation(memory_requirements, flags);
+        let allocation = self.create_allocation(memory_requirements, flags)?;
         let block_id = self.blocks.iter().position(|block| block.memory.as_raw() == allocation.block).unwrap();
-        self.device.bind_image_memory(image, self.blocks[block_id].memory, allocation.region).unwrap();
-        return allocation;
+        if let Err(error) = self.device.bind_image_memory(image, self.blocks[block_id].memory, allocation.region){
+            self.destroy_allocation(&allocation);
+            return Err(RenderError::from_vk(error, RenderError::Image));
+        }
+        return Ok(allocation);
     }
-    pub unsafe fn allocate_buffer_memory(&mut self, buffer : Buffer, flags : MemoryPropertyFlags) -> Allocation{
+    pub unsafe fn allocate_buffer_memory(&mut self, buffer : Buffer, flags : MemoryPropertyFlags) -> Result<Allocation, RenderError>{
         let memory_requirements = self.device.get_buffer_memory_requirements(buffer);
-        let allocation = self.create_allocation(memory_requirements, flags);
+        let allocation = self.create_allocation(memory_requirements, flags)?;
         let block_id = self.blocks.iter().position(|block| block.memory.as_raw() == allocation.block).unwrap();
-        self.device.bind_buffer_memory(buffer, self.blocks[block_id].memory, allocation.region).unwrap();
-        return allocation;
+        if let Err(error) = self.device.bind_buffer_memory(buffer, self.blocks[block_id].memory, allocation.region){
+            self.destroy_allocation(&allocation);
+            return Err(RenderError::from_vk(error, RenderError::Buffer));
+        }
+        return Ok(allocation);
+    }
+    ///Copies the data into a host visible allocation.
+    pub unsafe fn write(&mut self, allocation : &Allocation, data : &[u8]) -> Result<(), RenderError>{
+        let memory = self.blocks.iter().find(|block| block.memory.as_raw() == allocation.block).unwrap().memory;
+        let pointer = self.device.map_memory(memory, allocation.region, data.len() as u64, MemoryMapFlags::empty()).map_err(|error| RenderError::from_vk(error, RenderError::Buffer))?;
+        std::ptr::copy_nonoverlapping(data.as_ptr(), pointer as *mut u8, data.len());
+        self.device.unmap_memory(memory);
+        return Ok(());
     }
 }
\ No newline at end of file
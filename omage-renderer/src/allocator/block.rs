@@ -2,8 +2,9 @@ use std::cmp::max;
 
 use ash::Device;
 use ash::vk::{DeviceMemory, MemoryAllocateInfo, StructureType, Handle, MemoryPropertyFlags};
-use slog::{Logger, warn, crit};
+use slog::{Logger, warn};
 use crate::allocator::region::Region;
+use crate::error::RenderError;
 
 use super::Allocator;
 
@@ -24,7 +25,7 @@ impl Block{
         let memory = match device.allocate_memory(&allocate_info, None){
             Ok(memory) => {memory}
             Err(error) => {
-                warn!(logger, "[thread#{}]Failed to allocate GPU memory, {}.", rayon::current_thread_index().unwrap(), error);
+                warn!(logger, "Failed to allocate GPU memory, {}.", error);
                 return None;
             }
         };
@@ -41,17 +42,14 @@ impl Allocator{
         self.device.free_memory(self.blocks[self.blocks.iter().position(|block| block.memory.as_raw() == block_id).unwrap()].memory, None);
         self.blocks.remove(self.blocks.iter().position(|block| block.memory.as_raw() == block_id).unwrap());
     }
-    pub unsafe fn create_block(&mut self, size : u64, memory_type_filter : u32, memory_property_flags : MemoryPropertyFlags) -> u64{
+    pub unsafe fn create_block(&mut self, size : u64, memory_type_filter : u32, memory_property_flags : MemoryPropertyFlags) -> Result<u64, RenderError>{
         for memory_type in self.get_compatible_memory_types(memory_type_filter, memory_property_flags){
-            let block = Block::new(&self.logger, &self.device, max(super::MIN_BLOCK_SIZE, size), memory_type);
-            if block.is_some(){
-                let block = block.unwrap();
+            if let Some(block) = Block::new(&self.logger, &self.device, max(super::MIN_BLOCK_SIZE, size), memory_type){
                 let block_id = block.memory.as_raw();
                 self.blocks.push(block);
-                return block_id;
+                return Ok(block_id);
             }
         }
-        crit!(self.logger, "[thread#{}]Memory requested that does not exist.", rayon::current_thread_index().unwrap());
-        panic!();
+        return Err(RenderError::OutOfMemory);
     }
 }
\ No newline at end of file
@@ -17,7 +17,7 @@ pub struct Allocator{
 }
 impl Allocator{
     pub unsafe fn new(logger : &Logger, instance : &Instance, physical_device : PhysicalDevice, device : &Device) -> Self{
-        info!(logger, "[thread#{}]Successfully created the Vulkan memory allocator.", rayon::current_thread_index().unwrap());
+        info!(logger, "Successfully created the Vulkan memory allocator.");
         return Self{
             device:device.clone(),
             memory_properties:instance.get_physical_device_memory_properties(physical_device),
@@ -26,7 +26,7 @@ impl Allocator{
         }
     }
     pub unsafe fn destroy(&mut self){
-        info!(self.logger, "[thread#{}]Destroying the Vulkan memory allocator.", rayon::current_thread_index().unwrap());
+        info!(self.logger, "Destroying the Vulkan memory allocator.");
         for i in 0..self.blocks.len(){self.destroy_block(self.blocks[i].memory.as_raw())}
         self.blocks=vec!();
     }
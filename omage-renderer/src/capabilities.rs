@@ -0,0 +1,128 @@
+use std::collections::HashSet;
+use std::ffi::{CStr, CString};
+use ash::extensions::khr::Swapchain;
+use ash::vk::{ExtDescriptorIndexingFn, KhrCreateRenderpass2Fn, KhrDepthStencilResolveFn, KhrDynamicRenderingFn, KhrMaintenance2Fn, KhrMaintenance3Fn, KhrMultiviewFn, KhrSynchronization2Fn, KhrTimelineSemaphoreFn};
+use serde_derive::{Serialize, Deserialize};
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub enum ApiVersion{
+    #[serde(rename = "1.0")]
+    V1_0,
+    #[serde(rename = "1.1")]
+    V1_1,
+    #[serde(rename = "1.2")]
+    V1_2,
+    #[serde(rename = "1.3")]
+    V1_3,
+}
+impl ApiVersion{
+    pub fn raw(&self) -> u32{
+        return match self{
+            ApiVersion::V1_0 => {ash::vk::API_VERSION_1_0}
+            ApiVersion::V1_1 => {ash::vk::API_VERSION_1_1}
+            ApiVersion::V1_2 => {ash::vk::API_VERSION_1_2}
+            ApiVersion::V1_3 => {ash::vk::API_VERSION_1_3}
+        }
+    }
+    ///Rounds a raw Vulkan version down to the closest version the renderer knows about.
+    pub fn from_raw(version : u32) -> Self{
+        return match (ash::vk::api_version_major(version), ash::vk::api_version_minor(version)){
+            (0, _) | (1, 0) => {ApiVersion::V1_0}
+            (1, 1) => {ApiVersion::V1_1}
+            (1, 2) => {ApiVersion::V1_2}
+            _ => {ApiVersion::V1_3}
+        }
+    }
+}
+impl Default for ApiVersion{
+    fn default() -> Self {
+        return ApiVersion::V1_0;
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum DeviceFeature{
+    SamplerAnisotropy,
+    FillModeNonSolid,
+    DescriptorIndexing,
+    TimelineSemaphore,
+    DynamicRendering,
+    Synchronization2,
+}
+impl DeviceFeature{
+    ///The version that promoted the feature to core.
+    pub fn core_version(&self) -> ApiVersion{
+        return match self{
+            DeviceFeature::SamplerAnisotropy | DeviceFeature::FillModeNonSolid => {ApiVersion::V1_0}
+            DeviceFeature::DescriptorIndexing | DeviceFeature::TimelineSemaphore => {ApiVersion::V1_2}
+            DeviceFeature::DynamicRendering | DeviceFeature::Synchronization2 => {ApiVersion::V1_3}
+        }
+    }
+    ///The device extensions that have to be enabled alongside the feature on the given version.
+    pub fn extensions(&self, version : ApiVersion) -> Vec<&'static CStr>{
+        if self.core_version() <= version{return vec![]}
+        return match self{
+            DeviceFeature::SamplerAnisotropy | DeviceFeature::FillModeNonSolid => {vec![]}
+            DeviceFeature::DescriptorIndexing => {vec![KhrMaintenance3Fn::name(), ExtDescriptorIndexingFn::name()]}
+            DeviceFeature::TimelineSemaphore => {vec![KhrTimelineSemaphoreFn::name()]}
+            DeviceFeature::DynamicRendering => {vec![KhrMultiviewFn::name(), KhrMaintenance2Fn::name(), KhrCreateRenderpass2Fn::name(), KhrDepthStencilResolveFn::name(), KhrDynamicRenderingFn::name()]}
+            DeviceFeature::Synchronization2 => {vec![KhrSynchronization2Fn::name()]}
+        }
+    }
+}
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Requirement{
+    Required,
+    Optional,
+}
+///The features and extensions the renderer asks of a device, devices lacking a required item are never selected.
+#[derive(Clone)]
+pub struct DeviceRequirements{
+    pub features : Vec<(DeviceFeature, Requirement)>,
+    pub extensions : Vec<(CString, Requirement)>,
+}
+impl DeviceRequirements{
+    pub fn feature(mut self, feature : DeviceFeature, requirement : Requirement) -> Self{
+        self.features.retain(|(requested, _)| *requested != feature);
+        self.features.push((feature, requirement));
+        return self;
+    }
+    pub fn extension(mut self, name : &str, requirement : Requirement) -> Self{
+        let name = CString::new(name).unwrap();
+        self.extensions.retain(|(requested, _)| *requested != name);
+        self.extensions.push((name, requirement));
+        return self;
+    }
+}
+///Only asks for what the renderer itself uses, applications request the newer features they need on top.
+impl Default for DeviceRequirements{
+    fn default() -> Self {
+        return Self{
+            features : vec![
+                (DeviceFeature::SamplerAnisotropy, Requirement::Optional),
+                (DeviceFeature::FillModeNonSolid, Requirement::Optional),
+            ],
+            extensions : vec![(Swapchain::name().to_owned(), Requirement::Required)],
+        }
+    }
+}
+///The features and extensions that were enabled on the created device.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct DeviceCapabilities{
+    ///The name of the physical device, as reported by the driver.
+    pub device_name : String,
+    pub api_version : ApiVersion,
+    pub features : HashSet<DeviceFeature>,
+    pub extensions : Vec<CString>,
+}
+impl DeviceCapabilities{
+    pub fn supports_version(&self, version : ApiVersion) -> bool{
+        return self.api_version >= version;
+    }
+    pub fn has_feature(&self, feature : DeviceFeature) -> bool{
+        return self.features.contains(&feature);
+    }
+    pub fn has_extension(&self, name : &str) -> bool{
+        return self.extensions.iter().any(|extension| extension.to_str() == Ok(name));
+    }
+}
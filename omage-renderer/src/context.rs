@@ -0,0 +1,386 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use ash::{Device, Instance};
+use ash::extensions::khr::{GetPhysicalDeviceProperties2, Surface, Swapchain};
+use ash::vk::{AccessFlags, CommandBuffer, CommandBufferBeginInfo, CommandBufferResetFlags, CommandBufferUsageFlags, CommandPool, DependencyFlags, Fence, Framebuffer, ImageView, MemoryBarrier, PhysicalDevice, PipelineBindPoint, PipelineStageFlags, Queue, RenderPass, Semaphore, ShaderStageFlags, StructureType, SubmitInfo, SurfaceKHR, SwapchainKHR};
+use slog::Logger;
+use crate::allocator::Allocator;
+use crate::capabilities::{ApiVersion, DeviceCapabilities, DeviceRequirements};
+use crate::error::RenderError;
+use crate::functions;
+use crate::functions::device::QueueInfo;
+use crate::functions::swapchain::SwapchainInfo;
+use crate::instance::RenderConfig;
+use crate::objects::buffer::{AllocatedBuffer, BufferDescription};
+use crate::objects::image::{AllocatedImageView, ImageDescription};
+use crate::objects::pipeline::{ComputeDispatch, ComputePipeline, ComputePipelineDescription};
+use crate::objects::upload::{GpuAsset, GpuResource};
+
+///Everything that belongs to the logical device, torn down and rebuilt as a whole when the device is lost.
+pub struct DeviceContext{
+    pub capabilities : DeviceCapabilities,
+    pub device : Device,
+    pub swapchain_loader : Swapchain,
+    pub swapchain : SwapchainKHR,
+    pub allocator : Allocator,
+    pub swapchain_image_views : Vec<ImageView>,
+    pub depth_image : AllocatedImageView,
+    pub render_pass : RenderPass,
+    pub framebuffers : Vec<Framebuffer>,
+    pub images : Vec<AllocatedImageView>,
+    pub queue_info : QueueInfo,
+    pub graphics_queue : Queue,
+    pub compute_queue : Queue,
+    pub graphics_command_pool : CommandPool,
+    pub frame_command_buffer : CommandBuffer,
+    ///Signaled when the last submitted frame finished, waited on before the next one so a lost device surfaces on every frame.
+    pub frame_fence : Fence,
+    pub frame_pending : bool,
+    ///The number of frames submitted so far.
+    pub frames_submitted : u64,
+    ///Replaced or released objects with the number of frames submitted when they were retired, destroyed once a later frame finished.
+    pub retired : Vec<(u64, Retired)>,
+    pub compute_command_pool : CommandPool,
+    pub compute_command_buffer : CommandBuffer,
+    pub compute_fence : Fence,
+    pub compute_semaphore : Semaphore,
+    pub compute_pending : bool,
+    pub compute_pipelines : Vec<ComputePipeline>,
+    ///The buffers created through the renderer, by the id handed out for them.
+    pub buffers : HashMap<u64, AllocatedBuffer>,
+    pub gpu_assets : HashMap<u64, GpuResource>,
+}
+///An object that may still be used by submitted work.
+pub enum Retired{
+    Buffer(AllocatedBuffer),
+    Asset(GpuResource),
+}
+impl Retired{
+    unsafe fn destroy(&self, allocator : &mut Allocator){
+        match self{
+            Retired::Buffer(buffer) => {buffer.destroy(allocator)}
+            Retired::Asset(resource) => {resource.destroy(allocator)}
+        }
+    }
+}
+///The CPU side state of everything created through the renderer, the device context is rebuilt from it after a device loss.
+#[derive(Default)]
+pub struct Resources{
+    pub images : Vec<ImageDescription>,
+    pub compute_pipelines : Vec<ComputePipelineDescription>,
+    ///Buffers are recreated with undefined content, the compute work filling them has to run again.
+    pub buffers : HashMap<u64, BufferDescription>,
+    ///The buffer bound to each binding of a compute pipeline.
+    pub compute_bindings : HashMap<(usize, u32), u64>,
+    pub uploads : HashMap<u64, Arc<GpuAsset>>,
+}
+///The instance level objects a device context is created from.
+pub struct ContextInfo<'a>{
+    pub logger : &'a Logger,
+    pub instance : &'a Instance,
+    pub api_version : ApiVersion,
+    pub properties2 : Option<&'a GetPhysicalDeviceProperties2>,
+    pub surface_loader : &'a Surface,
+    pub surface : SurfaceKHR,
+    pub requirements : &'a DeviceRequirements,
+    pub config : &'a RenderConfig,
+}
+type SwapchainObjects = (SwapchainKHR, Vec<ImageView>, AllocatedImageView, RenderPass, Vec<Framebuffer>);
+
+impl DeviceContext{
+    ///Creates the device and all objects depending on it, recreating the registered images, buffers and uploaded assets from their CPU side data.
+    pub unsafe fn new(info : &ContextInfo, resources : &Resources) -> Result<Self, RenderError>{
+        let (physical_device, capabilities, queue_info, device) = Self::create_device(info)?;
+        let swapchain_loader = Swapchain::new(info.instance, &device);
+        let mut allocator = Allocator::new(info.logger, info.instance, physical_device, &device);
+        let (swapchain, swapchain_image_views, depth_image, render_pass, framebuffers) = match Self::create_swapchain_objects(info, physical_device, &device, &swapchain_loader, &mut allocator){
+            Ok(objects) => {objects}
+            Err(error) => {
+                allocator.destroy();
+                device.destroy_device(None);
+                return Err(error);
+            }
+        };
+        let mut context = Self{
+            graphics_queue : device.get_device_queue(queue_info.graphics_family, 0),
+            compute_queue : device.get_device_queue(queue_info.compute_family, 0),
+            graphics_command_pool : CommandPool::null(),
+            frame_command_buffer : CommandBuffer::null(),
+            frame_fence : Fence::null(),
+            frame_pending : false,
+            frames_submitted : 0,
+            retired : vec![],
+            compute_command_pool : CommandPool::null(),
+            compute_command_buffer : CommandBuffer::null(),
+            compute_fence : Fence::null(),
+            compute_semaphore : Semaphore::null(),
+            compute_pending : false,
+            compute_pipelines : vec![],
+            buffers : HashMap::new(),
+            images : vec![],
+            gpu_assets : HashMap::new(),
+            capabilities,device,swapchain_loader,swapchain,allocator,swapchain_image_views,depth_image,render_pass,framebuffers,queue_info,
+        };
+        if let Err(error) = context.create_resources(resources){
+            context.destroy();
+            return Err(error);
+        }
+        return Ok(context);
+    }
+    unsafe fn create_resources(&mut self, resources : &Resources) -> Result<(), RenderError>{
+        self.create_frame_objects()?;
+        self.create_compute_objects(&resources.compute_pipelines)?;
+        for description in resources.images.iter(){
+            self.images.push(AllocatedImageView::from_description(&mut self.allocator, description)?);
+        }
+        for (id, description) in resources.buffers.iter(){
+            self.create_buffer(*id, description)?;
+        }
+        for (&(pipeline, binding), &buffer) in resources.compute_bindings.iter(){
+            self.bind_compute_buffer(pipeline, binding, buffer)?;
+        }
+        for (id, asset) in resources.uploads.iter(){
+            self.upload(*id, asset)?;
+        }
+        return Ok(());
+    }
+    unsafe fn create_device(info : &ContextInfo) -> Result<(PhysicalDevice, DeviceCapabilities, QueueInfo, Device), RenderError>{
+        let physical_devices = functions::device::get_compatible_devices(info.instance, info.api_version, info.properties2, info.surface_loader, info.surface, info.requirements)?;
+        let physical_device = functions::device::select_physical_device(info.logger, info.instance, physical_devices, info.config);
+        let capabilities = functions::device::get_device_capabilities(info.instance, info.api_version, info.properties2, physical_device, info.requirements).ok_or(RenderError::NoCompatibleDevice)?;
+        let queue_info = functions::device::QueueInfo::new(info.instance, physical_device)?;
+        let device = functions::device::create_device(info.logger, info.instance, &queue_info, physical_device, &capabilities, false, true)?;
+        return Ok((physical_device, capabilities, queue_info, device));
+    }
+    ///Creates everything that depends on the swapchain, destroying the partially created objects on failure.
+    unsafe fn create_swapchain_objects(info : &ContextInfo, physical_device : PhysicalDevice, device : &Device, swapchain_loader : &Swapchain, allocator : &mut Allocator) -> Result<SwapchainObjects, RenderError>{
+        let swapchain_info = SwapchainInfo::new(info.instance, info.surface_loader, info.surface, physical_device, false)?;
+        let swapchain = functions::swapchain::create_swapchain(swapchain_loader, &swapchain_info, info.surface)?;
+        let swapchain_image_views = match swapchain_loader.get_swapchain_images(swapchain).map_err(|error| RenderError::from_vk(error, RenderError::Swapchain)).and_then(|images| crate::objects::image::create_swapchain_image_views(device, swapchain_info.format, &images)){
+            Ok(views) => {views}
+            Err(error) => {
+                swapchain_loader.destroy_swapchain(swapchain, None);
+                return Err(error);
+            }
+        };
+        let destroy_views = |views : &Vec<ImageView>|{
+            for &view in views.iter(){device.destroy_image_view(view, None)}
+            swapchain_loader.destroy_swapchain(swapchain, None);
+        };
+        let depth_image = match AllocatedImageView::new_depth(allocator, swapchain_info.extent, swapchain_info.depth_format){
+            Ok(depth_image) => {depth_image}
+            Err(error) => {
+                destroy_views(&swapchain_image_views);
+                return Err(error);
+            }
+        };
+        let render_pass = match functions::render_pass::create_render_pass(device, swapchain_info.format, swapchain_info.depth_format){
+            Ok(render_pass) => {render_pass}
+            Err(error) => {
+                depth_image.destroy(allocator);
+                destroy_views(&swapchain_image_views);
+                return Err(error);
+            }
+        };
+        let framebuffers = match functions::framebuffer::create_framebuffers(device, render_pass, &swapchain_image_views, depth_image.view, swapchain_info.extent){
+            Ok(framebuffers) => {framebuffers}
+            Err(error) => {
+                device.destroy_render_pass(render_pass, None);
+                depth_image.destroy(allocator);
+                destroy_views(&swapchain_image_views);
+                return Err(error);
+            }
+        };
+        return Ok((swapchain, swapchain_image_views, depth_image, render_pass, framebuffers));
+    }
+    unsafe fn create_frame_objects(&mut self) -> Result<(), RenderError>{
+        self.graphics_command_pool = functions::command::create_command_pool(&self.device, self.queue_info.graphics_family)?;
+        self.frame_command_buffer = functions::command::allocate_command_buffer(&self.device, self.graphics_command_pool)?;
+        self.frame_fence = functions::command::create_fence(&self.device, false)?;
+        return Ok(());
+    }
+    unsafe fn create_compute_objects(&mut self, compute_descriptions : &[ComputePipelineDescription]) -> Result<(), RenderError>{
+        self.compute_command_pool = functions::command::create_command_pool(&self.device, self.queue_info.compute_family)?;
+        self.compute_command_buffer = functions::command::allocate_command_buffer(&self.device, self.compute_command_pool)?;
+        self.compute_fence = functions::command::create_fence(&self.device, true)?;
+        self.compute_semaphore = functions::command::create_semaphore(&self.device)?;
+        for description in compute_descriptions.iter(){
+            self.compute_pipelines.push(ComputePipeline::new(&self.device, description)?);
+        }
+        return Ok(());
+    }
+    ///The queue families resources shared between graphics and compute have to be accessible from.
+    pub fn queue_families(&self) -> [u32; 2]{
+        return [self.queue_info.graphics_family, self.queue_info.compute_family];
+    }
+    ///Creates a buffer usable from both the graphics and the compute queue under the id.
+    pub unsafe fn create_buffer(&mut self, id : u64, description : &BufferDescription) -> Result<(), RenderError>{
+        let queue_families = self.queue_families();
+        let buffer = AllocatedBuffer::new(&mut self.allocator, description.size, description.usage, description.flags, &queue_families)?;
+        if let Some(old) = self.buffers.insert(id, buffer){self.retire(Retired::Buffer(old))}
+        return Ok(());
+    }
+    pub unsafe fn destroy_buffer(&mut self, id : u64){
+        if let Some(buffer) = self.buffers.remove(&id){self.retire(Retired::Buffer(buffer))}
+    }
+    ///Points a buffer binding of a compute pipeline at the whole buffer.
+    pub unsafe fn bind_compute_buffer(&mut self, pipeline : usize, binding : u32, buffer : u64) -> Result<(), RenderError>{
+        let pipeline = self.compute_pipelines.get(pipeline).ok_or(RenderError::UnknownPipeline(pipeline))?;
+        let buffer = self.buffers.get(&buffer).ok_or(RenderError::UnknownBuffer(buffer))?;
+        return pipeline.bind_buffer(&self.device, binding, buffer.buffer, 0, buffer.size);
+    }
+    ///Records the dispatches and submits them on the compute queue, the next graphics submission waits for them.
+    pub unsafe fn dispatch_compute(&mut self, dispatches : &[ComputeDispatch]) -> Result<(), RenderError>{
+        if let Some(dispatch) = dispatches.iter().find(|dispatch| dispatch.pipeline >= self.compute_pipelines.len()){
+            return Err(RenderError::UnknownPipeline(dispatch.pipeline));
+        }
+        if self.compute_pending{self.submit_frame()?}
+        let map_error = |error| RenderError::from_vk(error, RenderError::Command);
+        self.device.wait_for_fences(&[self.compute_fence], true, u64::MAX).map_err(map_error)?;
+        self.device.reset_fences(&[self.compute_fence]).map_err(map_error)?;
+        self.device.reset_command_buffer(self.compute_command_buffer, CommandBufferResetFlags::empty()).map_err(map_error)?;
+        let begin_info = CommandBufferBeginInfo{
+            s_type : StructureType::COMMAND_BUFFER_BEGIN_INFO,
+            p_next : std::ptr::null(),
+            flags : CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            p_inheritance_info : std::ptr::null(),
+        };
+        self.device.begin_command_buffer(self.compute_command_buffer, &begin_info).map_err(map_error)?;
+        for (i, dispatch) in dispatches.iter().enumerate(){
+            let pipeline = &self.compute_pipelines[dispatch.pipeline];
+            if i != 0{
+                //Later dispatches may read what earlier ones wrote.
+                let barrier = MemoryBarrier{
+                    s_type : StructureType::MEMORY_BARRIER,
+                    p_next : std::ptr::null(),
+                    src_access_mask : AccessFlags::SHADER_WRITE,
+                    dst_access_mask : AccessFlags::SHADER_READ | AccessFlags::SHADER_WRITE,
+                };
+                self.device.cmd_pipeline_barrier(self.compute_command_buffer, PipelineStageFlags::COMPUTE_SHADER, PipelineStageFlags::COMPUTE_SHADER, DependencyFlags::empty(), &[barrier], &[], &[]);
+            }
+            self.device.cmd_bind_pipeline(self.compute_command_buffer, PipelineBindPoint::COMPUTE, pipeline.pipeline);
+            if !pipeline.bindings.is_empty(){
+                self.device.cmd_bind_descriptor_sets(self.compute_command_buffer, PipelineBindPoint::COMPUTE, pipeline.layout, 0, &[pipeline.descriptor_set], &[]);
+            }
+            if !dispatch.push_constants.is_empty(){
+                self.device.cmd_push_constants(self.compute_command_buffer, pipeline.layout, ShaderStageFlags::COMPUTE, 0, &dispatch.push_constants);
+            }
+            self.device.cmd_dispatch(self.compute_command_buffer, dispatch.group_count[0], dispatch.group_count[1], dispatch.group_count[2]);
+        }
+        self.device.end_command_buffer(self.compute_command_buffer).map_err(map_error)?;
+        let submit_info = SubmitInfo{
+            s_type : StructureType::SUBMIT_INFO,
+            p_next : std::ptr::null(),
+            wait_semaphore_count : 0,
+            p_wait_semaphores : std::ptr::null(),
+            p_wait_dst_stage_mask : std::ptr::null(),
+            command_buffer_count : 1,
+            p_command_buffers : &self.compute_command_buffer,
+            signal_semaphore_count : 1,
+            p_signal_semaphores : &self.compute_semaphore,
+        };
+        self.device.queue_submit(self.compute_queue, &[submit_info], self.compute_fence).map_err(map_error)?;
+        self.compute_pending = true;
+        return Ok(());
+    }
+    ///Uploads an asset on the graphics queue, replacing the resource previously uploaded under the same id.
+    pub unsafe fn upload(&mut self, id : u64, asset : &GpuAsset) -> Result<(), RenderError>{
+        let resource = GpuResource::new(&self.device, &mut self.allocator, self.graphics_queue, self.queue_info.graphics_family, asset)?;
+        if let Some(old) = self.gpu_assets.insert(id, resource){self.retire(Retired::Asset(old))}
+        return Ok(());
+    }
+    pub unsafe fn release(&mut self, id : u64){
+        if let Some(resource) = self.gpu_assets.remove(&id){self.retire(Retired::Asset(resource))}
+    }
+    ///Defers destroying the object until the frames that may use it finished, instead of waiting for the whole device.
+    fn retire(&mut self, object : Retired){
+        self.retired.push((self.frames_submitted, object));
+    }
+    ///Destroys the retired objects no submitted work can use anymore.
+    unsafe fn destroy_retired(&mut self, frames_completed : u64){
+        //The first frame submitted after the retirement also waited for the compute work submitted before it.
+        let (done, pending) : (Vec<_>, Vec<_>) = std::mem::take(&mut self.retired).into_iter().partition(|(retired_at, _)| *retired_at < frames_completed);
+        for (_, object) in done.iter(){object.destroy(&mut self.allocator)}
+        self.retired = pending;
+    }
+    ///Submits the graphics work of a frame, its shaders and vertex input wait for outstanding compute work.
+    ///The previous frame is waited for first, so a lost device is noticed even on frames without other work.
+    pub unsafe fn submit_frame(&mut self) -> Result<(), RenderError>{
+        let map_error = |error| RenderError::from_vk(error, RenderError::Command);
+        if self.frame_pending{
+            self.device.wait_for_fences(&[self.frame_fence], true, u64::MAX).map_err(map_error)?;
+            self.frame_pending = false;
+        }
+        self.destroy_retired(self.frames_submitted);
+        self.device.reset_command_buffer(self.frame_command_buffer, CommandBufferResetFlags::empty()).map_err(map_error)?;
+        let begin_info = CommandBufferBeginInfo{
+            s_type : StructureType::COMMAND_BUFFER_BEGIN_INFO,
+            p_next : std::ptr::null(),
+            flags : CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            p_inheritance_info : std::ptr::null(),
+        };
+        self.device.begin_command_buffer(self.frame_command_buffer, &begin_info).map_err(map_error)?;
+        self.device.end_command_buffer(self.frame_command_buffer).map_err(map_error)?;
+        //Compute results are consumed as vertices or by shaders, everything before those stages overlaps with the compute queue.
+        let wait_stage = PipelineStageFlags::VERTEX_INPUT | PipelineStageFlags::VERTEX_SHADER | PipelineStageFlags::FRAGMENT_SHADER;
+        let wait_semaphore_count = self.compute_pending as u32;
+        let submit_info = SubmitInfo{
+            s_type : StructureType::SUBMIT_INFO,
+            p_next : std::ptr::null(),
+            wait_semaphore_count,
+            p_wait_semaphores : &self.compute_semaphore,
+            p_wait_dst_stage_mask : &wait_stage,
+            command_buffer_count : 1,
+            p_command_buffers : &self.frame_command_buffer,
+            signal_semaphore_count : 0,
+            p_signal_semaphores : std::ptr::null(),
+        };
+        self.device.reset_fences(&[self.frame_fence]).map_err(map_error)?;
+        self.device.queue_submit(self.graphics_queue, &[submit_info], self.frame_fence).map_err(map_error)?;
+        self.frame_pending = true;
+        self.frames_submitted += 1;
+        self.compute_pending = false;
+        return Ok(());
+    }
+    unsafe fn destroy_swapchain(&mut self){
+        for &framebuffer in self.framebuffers.iter(){
+            self.device.destroy_framebuffer(framebuffer, None);
+        }
+        self.depth_image.destroy(&mut self.allocator);
+        for &swapchain_image_view in self.swapchain_image_views.iter(){
+            self.device.destroy_image_view(swapchain_image_view, None);
+        }
+        self.swapchain_image_views=vec!();
+        self.swapchain_loader.destroy_swapchain(self.swapchain, None);
+    }
+    ///Destroys every object in the context, also safe to call on a lost device.
+    pub unsafe fn destroy(&mut self){
+        let _ = self.device.device_wait_idle();
+        self.destroy_retired(u64::MAX);
+        for pipeline in self.compute_pipelines.iter(){
+            pipeline.destroy(&self.device);
+        }
+        self.compute_pipelines=vec!();
+        self.device.destroy_semaphore(self.compute_semaphore, None);
+        self.device.destroy_fence(self.compute_fence, None);
+        self.device.destroy_command_pool(self.compute_command_pool, None);
+        self.device.destroy_fence(self.frame_fence, None);
+        self.device.destroy_command_pool(self.graphics_command_pool, None);
+        for image in self.images.iter(){
+            image.destroy(&mut self.allocator);
+        }
+        self.images=vec!();
+        for buffer in self.buffers.values(){
+            buffer.destroy(&mut self.allocator);
+        }
+        self.buffers.clear();
+        for resource in self.gpu_assets.values(){
+            resource.destroy(&mut self.allocator);
+        }
+        self.gpu_assets.clear();
+        self.destroy_swapchain();
+        self.allocator.destroy();
+        self.device.destroy_render_pass(self.render_pass, None);
+        self.device.destroy_device(None);
+    }
+}
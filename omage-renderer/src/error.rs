@@ -0,0 +1,69 @@
+use std::fmt::{Display, Formatter};
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum RenderError{
+    DriverLoad(String),
+    Instance(ash::vk::Result),
+    Surface(ash::vk::Result),
+    NoCompatibleDevice,
+    Device(ash::vk::Result),
+    QueueFamilies,
+    Swapchain(ash::vk::Result),
+    DepthFormat,
+    RenderPass(ash::vk::Result),
+    Framebuffer(ash::vk::Result),
+    Image(ash::vk::Result),
+    Buffer(ash::vk::Result),
+    Pipeline(ash::vk::Result),
+    Command(ash::vk::Result),
+    OutOfMemory,
+    DeviceLost,
+    ///No compute pipeline was registered under the index.
+    UnknownPipeline(usize),
+    ///The compute pipeline has no binding with the index.
+    UnknownBinding(u32),
+    ///No buffer was created under the id.
+    UnknownBuffer(u64),
+    ///The render thread is gone, so nothing can be sent to it anymore.
+    Disconnected,
+    ///The render thread panicked, with the panic message.
+    Panicked(String),
+}
+impl RenderError{
+    ///Maps the Vulkan out of memory and device lost codes to their own variants, everything else to the given variant.
+    pub fn from_vk(error : ash::vk::Result, variant : fn(ash::vk::Result) -> Self) -> Self{
+        return match error{
+            ash::vk::Result::ERROR_OUT_OF_HOST_MEMORY | ash::vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => {RenderError::OutOfMemory}
+            ash::vk::Result::ERROR_DEVICE_LOST => {RenderError::DeviceLost}
+            error => {variant(error)}
+        }
+    }
+}
+impl Display for RenderError{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return match self{
+            RenderError::DriverLoad(error) => {write!(f, "failed to load the Vulkan driver, {}", error)}
+            RenderError::Instance(error) => {write!(f, "failed to create the Vulkan instance, {}", error)}
+            RenderError::Surface(error) => {write!(f, "failed to create the Vulkan surface, {}", error)}
+            RenderError::NoCompatibleDevice => {write!(f, "no compatible Vulkan device was found")}
+            RenderError::Device(error) => {write!(f, "failed to create the Vulkan device, {}", error)}
+            RenderError::QueueFamilies => {write!(f, "failed to assign queue families")}
+            RenderError::Swapchain(error) => {write!(f, "failed to create the swapchain, {}", error)}
+            RenderError::DepthFormat => {write!(f, "no supported depth format was found")}
+            RenderError::RenderPass(error) => {write!(f, "failed to create the render pass, {}", error)}
+            RenderError::Framebuffer(error) => {write!(f, "failed to create a framebuffer, {}", error)}
+            RenderError::Image(error) => {write!(f, "failed to create an image, {}", error)}
+            RenderError::Buffer(error) => {write!(f, "failed to create a buffer, {}", error)}
+            RenderError::Pipeline(error) => {write!(f, "failed to create a pipeline, {}", error)}
+            RenderError::Command(error) => {write!(f, "failed to record or submit commands, {}", error)}
+            RenderError::OutOfMemory => {write!(f, "out of memory")}
+            RenderError::DeviceLost => {write!(f, "the Vulkan device was lost")}
+            RenderError::UnknownPipeline(index) => {write!(f, "no compute pipeline is registered under {}", index)}
+            RenderError::UnknownBinding(binding) => {write!(f, "the compute pipeline has no binding {}", binding)}
+            RenderError::UnknownBuffer(id) => {write!(f, "no buffer exists under {}", id)}
+            RenderError::Disconnected => {write!(f, "the render thread is not running")}
+            RenderError::Panicked(message) => {write!(f, "the render thread panicked, {}", message)}
+        }
+    }
+}
+impl std::error::Error for RenderError{}
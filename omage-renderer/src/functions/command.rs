@@ -0,0 +1,39 @@
+use ash::Device;
+use ash::vk::{CommandBuffer, CommandBufferAllocateInfo, CommandBufferLevel, CommandPool, CommandPoolCreateFlags, CommandPoolCreateInfo, Fence, FenceCreateFlags, FenceCreateInfo, Semaphore, SemaphoreCreateFlags, SemaphoreCreateInfo, StructureType};
+use crate::error::RenderError;
+
+pub unsafe fn create_command_pool(device : &Device, queue_family_index : u32) -> Result<CommandPool, RenderError>{
+    let command_pool_create_info = CommandPoolCreateInfo{
+        s_type : StructureType::COMMAND_POOL_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+        queue_family_index,
+    };
+    return device.create_command_pool(&command_pool_create_info, None).map_err(|error| RenderError::from_vk(error, RenderError::Command));
+}
+pub unsafe fn allocate_command_buffer(device : &Device, command_pool : CommandPool) -> Result<CommandBuffer, RenderError>{
+    let allocate_info = CommandBufferAllocateInfo{
+        s_type : StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+        p_next : std::ptr::null(),
+        command_pool,
+        level : CommandBufferLevel::PRIMARY,
+        command_buffer_count : 1,
+    };
+    return Ok(device.allocate_command_buffers(&allocate_info).map_err(|error| RenderError::from_vk(error, RenderError::Command))?[0]);
+}
+pub unsafe fn create_fence(device : &Device, signaled : bool) -> Result<Fence, RenderError>{
+    let fence_create_info = FenceCreateInfo{
+        s_type : StructureType::FENCE_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : if signaled{FenceCreateFlags::SIGNALED}else{FenceCreateFlags::empty()},
+    };
+    return device.create_fence(&fence_create_info, None).map_err(|error| RenderError::from_vk(error, RenderError::Command));
+}
+pub unsafe fn create_semaphore(device : &Device) -> Result<Semaphore, RenderError>{
+    let semaphore_create_info = SemaphoreCreateInfo{
+        s_type : StructureType::SEMAPHORE_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : SemaphoreCreateFlags::empty(),
+    };
+    return device.create_semaphore(&semaphore_create_info, None).map_err(|error| RenderError::from_vk(error, RenderError::Command));
+}
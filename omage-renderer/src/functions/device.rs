@@ -1,42 +1,108 @@
-use std::ffi::CStr;
+use std::collections::HashSet;
+use std::ffi::{c_void, CStr};
 use ash::{vk::{PhysicalDevice, SurfaceKHR, QueueFlags}, Instance, extensions::khr::Surface, Device};
-use ash::extensions::khr::Swapchain;
-use ash::vk::{DeviceCreateFlags, DeviceCreateInfo, DeviceQueueCreateFlags, DeviceQueueCreateInfo, PhysicalDeviceFeatures, PhysicalDeviceType, StructureType};
-use slog::{Logger, crit, info};
+use ash::extensions::khr::GetPhysicalDeviceProperties2;
+use ash::vk::{DeviceCreateFlags, DeviceCreateInfo, DeviceQueueCreateFlags, DeviceQueueCreateInfo, PhysicalDeviceDescriptorIndexingFeatures, PhysicalDeviceDynamicRenderingFeatures, PhysicalDeviceFeatures, PhysicalDeviceFeatures2, PhysicalDeviceSynchronization2Features, PhysicalDeviceTimelineSemaphoreFeatures, PhysicalDeviceType, StructureType};
+use slog::{Logger, info};
 
+use crate::capabilities::{ApiVersion, DeviceCapabilities, DeviceFeature, DeviceRequirements, Requirement};
+use crate::error::RenderError;
 use crate::instance::RenderConfig;
 
-pub unsafe fn get_compatible_devices(logger : &Logger, instance : &Instance, surface_loader : &Surface, surface : SurfaceKHR) -> Vec<PhysicalDevice>{
-    let devices = match instance.enumerate_physical_devices(){
-        Ok(devices) => {devices}
-        Err(error) => {
-            crit!(logger, "Failed to get Vulkan devices, {}.", error);
-            panic!();
-        }
-    };
-    return devices.iter().filter_map(|device|{
+pub unsafe fn get_compatible_devices(instance : &Instance, instance_version : ApiVersion, properties2 : Option<&GetPhysicalDeviceProperties2>, surface_loader : &Surface, surface : SurfaceKHR, requirements : &DeviceRequirements) -> Result<Vec<PhysicalDevice>, RenderError>{
+    let devices = instance.enumerate_physical_devices().map_err(|error| RenderError::from_vk(error, RenderError::Device))?;
+    let devices = devices.iter().filter_map(|device|{
         let mut graphics_support = false;
         let mut compute_support = false;
         let mut surface_support = false;
         for (i, queue_family) in instance.get_physical_device_queue_family_properties(*device).iter().enumerate(){
             graphics_support = graphics_support || queue_family.queue_flags.contains(QueueFlags::GRAPHICS);
             compute_support = compute_support || queue_family.queue_flags.contains(QueueFlags::COMPUTE);
-            surface_support = surface_support || surface_loader.get_physical_device_surface_support(*device, i as u32, surface).unwrap() && queue_family.queue_flags.contains(QueueFlags::GRAPHICS);
+            surface_support = surface_support || surface_loader.get_physical_device_surface_support(*device, i as u32, surface).unwrap_or(false) && queue_family.queue_flags.contains(QueueFlags::GRAPHICS);
         }
-        if graphics_support && compute_support && surface_support{
+        let capabilities = get_device_capabilities(instance, instance_version, properties2, *device, requirements);
+        if graphics_support && compute_support && surface_support && capabilities.is_some(){
             Some(*device)
         }
         else{
             None
         }
     }).collect::<Vec<_>>();
+    if devices.is_empty(){return Err(RenderError::NoCompatibleDevice)}
+    return Ok(devices);
+}
+///Returns the requested capabilities the device supports, or None if a required one is missing.
+pub unsafe fn get_device_capabilities(instance : &Instance, instance_version : ApiVersion, properties2 : Option<&GetPhysicalDeviceProperties2>, device : PhysicalDevice, requirements : &DeviceRequirements) -> Option<DeviceCapabilities>{
+    let properties = instance.get_physical_device_properties(device);
+    let api_version = instance_version.min(ApiVersion::from_raw(properties.api_version));
+    let available_extensions = match instance.enumerate_device_extension_properties(device){
+        Ok(extensions) => {extensions.iter().map(|extension| CStr::from_ptr(extension.extension_name.as_ptr()).to_owned()).collect::<HashSet<_>>()}
+        Err(_) => {return None}
+    };
+    let features = instance.get_physical_device_features(device);
+    let mut descriptor_indexing = PhysicalDeviceDescriptorIndexingFeatures::default();
+    let mut timeline_semaphore = PhysicalDeviceTimelineSemaphoreFeatures::default();
+    let mut dynamic_rendering = PhysicalDeviceDynamicRenderingFeatures::default();
+    let mut synchronization2 = PhysicalDeviceSynchronization2Features::default();
+    //The feature structs are only chained when the version or the extensions of the device know them.
+    let known = |feature : DeviceFeature| feature.extensions(api_version).iter().all(|&extension| available_extensions.contains(extension));
+    let mut p_next : *mut c_void = std::ptr::null_mut();
+    if known(DeviceFeature::DescriptorIndexing){
+        descriptor_indexing.p_next = p_next;
+        p_next = &mut descriptor_indexing as *mut _ as *mut c_void;
+    }
+    if known(DeviceFeature::TimelineSemaphore){
+        timeline_semaphore.p_next = p_next;
+        p_next = &mut timeline_semaphore as *mut _ as *mut c_void;
+    }
+    if known(DeviceFeature::DynamicRendering){
+        dynamic_rendering.p_next = p_next;
+        p_next = &mut dynamic_rendering as *mut _ as *mut c_void;
+    }
+    if known(DeviceFeature::Synchronization2){
+        synchronization2.p_next = p_next;
+        p_next = &mut synchronization2 as *mut _ as *mut c_void;
+    }
+    let mut features2 = PhysicalDeviceFeatures2{p_next, ..Default::default()};
+    if instance_version >= ApiVersion::V1_1{instance.get_physical_device_features2(device, &mut features2)}
+    else if let Some(properties2) = properties2{properties2.get_physical_device_features2(device, &mut features2)}
+    let device_name = CStr::from_ptr(properties.device_name.as_ptr()).to_string_lossy().into_owned();
+    let mut capabilities = DeviceCapabilities{device_name, api_version, ..Default::default()};
+    for &(feature, requirement) in requirements.features.iter(){
+        let supported = match feature{
+            DeviceFeature::SamplerAnisotropy => {features.sampler_anisotropy == 1}
+            DeviceFeature::FillModeNonSolid => {features.fill_mode_non_solid == 1}
+            DeviceFeature::DescriptorIndexing => {
+                descriptor_indexing.runtime_descriptor_array == 1 && descriptor_indexing.descriptor_binding_partially_bound == 1 &&
+                descriptor_indexing.descriptor_binding_variable_descriptor_count == 1 && descriptor_indexing.shader_sampled_image_array_non_uniform_indexing == 1 &&
+                descriptor_indexing.descriptor_binding_sampled_image_update_after_bind == 1
+            }
+            DeviceFeature::TimelineSemaphore => {timeline_semaphore.timeline_semaphore == 1}
+            DeviceFeature::DynamicRendering => {dynamic_rendering.dynamic_rendering == 1}
+            DeviceFeature::Synchronization2 => {synchronization2.synchronization2 == 1}
+        } && feature.extensions(api_version).iter().all(|&extension| available_extensions.contains(extension));
+        if supported{
+            capabilities.features.insert(feature);
+            for extension in feature.extensions(api_version){
+                if !capabilities.extensions.iter().any(|enabled| enabled.as_c_str() == extension){capabilities.extensions.push(extension.to_owned())}
+            }
+        }
+        else if requirement == Requirement::Required{return None}
+    }
+    for (extension, requirement) in requirements.extensions.iter(){
+        if available_extensions.contains(extension){
+            if !capabilities.extensions.contains(extension){capabilities.extensions.push(extension.clone())}
+        }
+        else if *requirement == Requirement::Required{return None}
+    }
+    return Some(capabilities);
 }
 pub unsafe fn select_physical_device(logger : &Logger, instance : &Instance, supported_devices : Vec<PhysicalDevice>, config : &RenderConfig) -> PhysicalDevice{
     for device in supported_devices.iter(){
         let device_properties = instance.get_physical_device_properties(*device);
         let device_name = CStr::from_ptr(device_properties.device_name.as_ptr());
         let device_name = device_name.to_str().unwrap();
-        if device_name == config.gpu{info!(logger, "[thread#{}]Using selected device {}.", rayon::current_thread_index().unwrap() , device_name);return *device}
+        if device_name == config.gpu{info!(logger, "Using selected device {}.", device_name);return *device}
     }
     for device in supported_devices.iter() {
         let device_properties = instance.get_physical_device_properties(*device);
@@ -45,33 +111,27 @@ pub unsafe fn select_physical_device(logger : &Logger, instance : &Instance, sup
     return supported_devices[0];
 }
 pub struct QueueInfo{
-    graphics_family : u32,
-    compute_family : u32,
-    transfer_family : u32,
+    pub graphics_family : u32,
+    pub compute_family : u32,
+    pub transfer_family : u32,
 }
 impl QueueInfo{
-    pub unsafe fn new(logger : &Logger, instance : &Instance, device : PhysicalDevice) -> Self{
+    pub unsafe fn new(instance : &Instance, device : PhysicalDevice) -> Result<Self, RenderError>{
         let mut graphics_family = None;
         let mut compute_family = None;
         let mut transfer_family = None;
         for (i, queue_family) in instance.get_physical_device_queue_family_properties(device).iter().enumerate(){
              if queue_family.queue_flags.contains(QueueFlags::GRAPHICS) && graphics_family.is_none(){graphics_family = Some(i as u32)}
-             if queue_family.queue_flags.contains(QueueFlags::COMPUTE) && compute_family.is_none(){compute_family = Some(i as u32)}
-             else if queue_family.queue_flags.contains(QueueFlags::COMPUTE) && !queue_family.queue_flags.contains(QueueFlags::GRAPHICS){compute_family = Some(i as u32)}
-             if queue_family.queue_flags.contains(QueueFlags::TRANSFER) && transfer_family.is_none(){transfer_family = Some(i as u32)}
-            else if queue_family.queue_flags.contains(QueueFlags::TRANSFER) && !queue_family.queue_flags.contains(QueueFlags::COMPUTE) && !queue_family.queue_flags.contains(QueueFlags::GRAPHICS){transfer_family = Some(i as u32)}
-        }
-        if graphics_family.is_none() || compute_family.is_none() || transfer_family.is_none(){
-            crit!(logger, "Failed to assign queue families");
+            if queue_family.queue_flags.contains(QueueFlags::COMPUTE) && (compute_family.is_none() || !queue_family.queue_flags.contains(QueueFlags::GRAPHICS)){compute_family = Some(i as u32)}
+            if queue_family.queue_flags.contains(QueueFlags::TRANSFER) && (transfer_family.is_none() || !queue_family.queue_flags.intersects(QueueFlags::COMPUTE | QueueFlags::GRAPHICS)){transfer_family = Some(i as u32)}
         }
-        return Self{
-            graphics_family : graphics_family.unwrap(),
-            compute_family : compute_family.unwrap(),
-            transfer_family : transfer_family.unwrap(),
+        return match (graphics_family, compute_family, transfer_family){
+            (Some(graphics_family), Some(compute_family), Some(transfer_family)) => {Ok(Self{graphics_family, compute_family, transfer_family})}
+            _ => {Err(RenderError::QueueFamilies)}
         }
     }
 }
-pub unsafe fn create_device(logger : &Logger, instance : &Instance, queue_info : &QueueInfo, device : PhysicalDevice, dedicated_transfer_family : bool, dedicated_compute_family : bool) -> Device{
+pub unsafe fn create_device(logger : &Logger, instance : &Instance, queue_info : &QueueInfo, device : PhysicalDevice, capabilities : &DeviceCapabilities, dedicated_transfer_family : bool, dedicated_compute_family : bool) -> Result<Device, RenderError>{
     let priorities = [1.0];
     let mut device_queue_families = vec![DeviceQueueCreateInfo{
         s_type : StructureType::DEVICE_QUEUE_CREATE_INFO,
@@ -101,25 +161,56 @@ pub unsafe fn create_device(logger : &Logger, instance : &Instance, queue_info :
             queue_family_index : queue_info.transfer_family,
         });
     }
-    let extensions = [Swapchain::name().as_ptr()];
-    let features = PhysicalDeviceFeatures::default();
+    let extensions = capabilities.extensions.iter().map(|extension| extension.as_ptr()).collect::<Vec<_>>();
+    let features = PhysicalDeviceFeatures{
+        sampler_anisotropy : capabilities.has_feature(DeviceFeature::SamplerAnisotropy) as u32,
+        fill_mode_non_solid : capabilities.has_feature(DeviceFeature::FillModeNonSolid) as u32,
+        ..Default::default()
+    };
+    let mut descriptor_indexing = PhysicalDeviceDescriptorIndexingFeatures{
+        runtime_descriptor_array : 1,
+        descriptor_binding_partially_bound : 1,
+        descriptor_binding_variable_descriptor_count : 1,
+        shader_sampled_image_array_non_uniform_indexing : 1,
+        descriptor_binding_sampled_image_update_after_bind : 1,
+        ..Default::default()
+    };
+    let mut timeline_semaphore = PhysicalDeviceTimelineSemaphoreFeatures{timeline_semaphore : 1, ..Default::default()};
+    let mut dynamic_rendering = PhysicalDeviceDynamicRenderingFeatures{dynamic_rendering : 1, ..Default::default()};
+    let mut synchronization2 = PhysicalDeviceSynchronization2Features{synchronization2 : 1, ..Default::default()};
+    let mut p_next : *mut c_void = std::ptr::null_mut();
+    if capabilities.has_feature(DeviceFeature::DescriptorIndexing){
+        descriptor_indexing.p_next = p_next;
+        p_next = &mut descriptor_indexing as *mut _ as *mut c_void;
+    }
+    if capabilities.has_feature(DeviceFeature::TimelineSemaphore){
+        timeline_semaphore.p_next = p_next;
+        p_next = &mut timeline_semaphore as *mut _ as *mut c_void;
+    }
+    if capabilities.has_feature(DeviceFeature::DynamicRendering){
+        dynamic_rendering.p_next = p_next;
+        p_next = &mut dynamic_rendering as *mut _ as *mut c_void;
+    }
+    if capabilities.has_feature(DeviceFeature::Synchronization2){
+        synchronization2.p_next = p_next;
+        p_next = &mut synchronization2 as *mut _ as *mut c_void;
+    }
     let device_create_info = DeviceCreateInfo{
         s_type : StructureType::DEVICE_CREATE_INFO,
-        p_next : std::ptr::null(),
+        p_next,
         flags : DeviceCreateFlags::empty(),
         enabled_extension_count : extensions.len() as u32,
         pp_enabled_extension_names : extensions.as_ptr(),
-        enabled_layer_count : 0,
-        pp_enabled_layer_names : std::ptr::null(),
         p_queue_create_infos : device_queue_families.as_ptr(),
         queue_create_info_count : device_queue_families.len() as u32,
         p_enabled_features : &features,
+        ..Default::default()
     };
     return match instance.create_device(device, &device_create_info, None){
-        Ok(device) => {device}
-        Err(error) => {
-            crit!(logger, "[thread#{}]Failed to create device, {}.", rayon::current_thread_index().unwrap() , error);
-            panic!();
+        Ok(device) => {
+            info!(logger, "Created a Vulkan {:?} device with features {:?} and extensions {:?}.", capabilities.api_version, capabilities.features, capabilities.extensions);
+            Ok(device)
         }
+        Err(error) => {Err(RenderError::from_vk(error, RenderError::Device))}
     }
 }
\ No newline at end of file
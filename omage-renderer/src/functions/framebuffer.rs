@@ -1,8 +1,8 @@
 use ash::Device;
 use ash::vk::{Extent2D, Framebuffer, FramebufferCreateFlags, FramebufferCreateInfo, ImageView, RenderPass, StructureType};
-use slog::{crit, Logger};
+use crate::error::RenderError;
 
-pub unsafe fn create_framebuffers(logger : &Logger, device : &Device, render_pass : RenderPass, swapchain_images : &Vec<ImageView>, depth_image_view : ImageView, extent : Extent2D) -> Vec<Framebuffer>{
+pub unsafe fn create_framebuffers(device : &Device, render_pass : RenderPass, swapchain_images : &[ImageView], depth_image_view : ImageView, extent : Extent2D) -> Result<Vec<Framebuffer>, RenderError>{
     let mut framebuffers = vec!();
     for &swapchain_image in swapchain_images.iter(){
         let attachments = [swapchain_image, depth_image_view];
@@ -17,13 +17,13 @@ pub unsafe fn create_framebuffers(logger : &Logger, device : &Device, render_pas
             layers : 1,
             render_pass,
         };
-        framebuffers.push(match device.create_framebuffer(&framebuffer_create_info, None){
-            Ok(framebuffer) => {framebuffer}
+        match device.create_framebuffer(&framebuffer_create_info, None){
+            Ok(framebuffer) => {framebuffers.push(framebuffer)}
             Err(error) => {
-                crit!(logger, "[thread#{}]Failed to create framebuffer, {}.", rayon::current_thread_index().unwrap(), error);
-                panic!();
+                for &framebuffer in framebuffers.iter(){device.destroy_framebuffer(framebuffer, None)}
+                return Err(RenderError::from_vk(error, RenderError::Framebuffer));
             }
-        });
+        }
     }
-    return framebuffers;
+    return Ok(framebuffers);
 }
\ No newline at end of file
@@ -1,25 +1,43 @@
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
 use ash::{Entry, Instance};
 use ash::vk::{ApplicationInfo, InstanceCreateFlags, InstanceCreateInfo, StructureType};
-use slog::{crit, Logger};
 use winit::window::Window;
+use crate::capabilities::ApiVersion;
+use crate::error::RenderError;
 
-pub unsafe fn create_instance(logger : &Logger, entry : &Entry, debugging : bool, window : &Window) -> Option<Instance>{
-    let name = CString::new("omage").unwrap();
+///Packs a major.minor.patch version string, missing or invalid parts count as 0.
+fn pack_version(version : &str) -> u32{
+    let mut version = version.split('.').map(|part| part.parse().unwrap_or(0));
+    return ash::vk::make_api_version(0, version.next().unwrap_or(0), version.next().unwrap_or(0), version.next().unwrap_or(0));
+}
+
+pub fn supports_instance_extension(entry : &Entry, name : &CStr) -> bool{
+    return match entry.enumerate_instance_extension_properties(None){
+        Ok(extensions) => {extensions.iter().any(|extension| unsafe{CStr::from_ptr(extension.extension_name.as_ptr())} == name)}
+        Err(_) => {false}
+    }
+}
+pub fn get_instance_version(entry : &Entry) -> ApiVersion{
+    return match entry.try_enumerate_instance_version(){
+        Ok(Some(version)) => {ApiVersion::from_raw(version)}
+        _ => {ApiVersion::V1_0}
+    }
+}
+pub unsafe fn create_instance(entry : &Entry, app_name : &str, app_version : &str, api_version : ApiVersion, validation : bool, window : &Window, extensions : &[&CStr]) -> Result<Instance, RenderError>{
+    let app_name = CString::new(app_name).map_err(|_| RenderError::Instance(ash::vk::Result::ERROR_INITIALIZATION_FAILED))?;
+    let engine_name = CString::new("omage").unwrap();
     let validation_layer = CString::new("VK_LAYER_KHRONOS_validation").unwrap();
-    let enabled_layers = if debugging{vec![validation_layer.as_ptr()]}else{vec![]};
-    let window_extensions = match ash_window::enumerate_required_extensions(window) {Ok(layers) => {layers} Err(error) => {
-        crit!(logger, "Failed to get Vulkan window extensions, {}." , error);
-        panic!();
-    }};
+    let enabled_layers = if validation{vec![validation_layer.as_ptr()]}else{vec![]};
+    let mut enabled_extensions = ash_window::enumerate_required_extensions(window).map_err(|error| RenderError::from_vk(error, RenderError::Instance))?.to_vec();
+    enabled_extensions.extend(extensions.iter().map(|extension| extension.as_ptr()));
     let app_info = ApplicationInfo{
         s_type : StructureType::APPLICATION_INFO,
         p_next : std::ptr::null(),
-        api_version : ash::vk::API_VERSION_1_0,
-        application_version : 0,
-        engine_version : 0,
-        p_application_name : name.as_ptr(),
-        p_engine_name : name.as_ptr(),
+        api_version : api_version.raw(),
+        application_version : pack_version(app_version),
+        engine_version : pack_version(env!("CARGO_PKG_VERSION")),
+        p_application_name : app_name.as_ptr(),
+        p_engine_name : engine_name.as_ptr(),
     };
     let instance_create_info = InstanceCreateInfo{
         s_type : StructureType::INSTANCE_CREATE_INFO,
@@ -28,19 +46,12 @@ pub unsafe fn create_instance(logger : &Logger, entry : &Entry, debugging : bool
         p_application_info : &app_info,
         pp_enabled_layer_names : enabled_layers.as_ptr(),
         enabled_layer_count : enabled_layers.len() as u32,
-        pp_enabled_extension_names : window_extensions.as_ptr(),
-        enabled_extension_count : window_extensions.len() as u32,
+        pp_enabled_extension_names : enabled_extensions.as_ptr(),
+        enabled_extension_count : enabled_extensions.len() as u32,
     };
-    match entry.create_instance(&instance_create_info, None){
-        Ok(instance) => {Some(instance)}
-        Err(error) => {
-            match error{
-                ash::vk::Result::ERROR_LAYER_NOT_PRESENT => {create_instance(logger, entry, debugging, window)}
-                _ => {
-                    crit!(logger, "[thread#{}]Failed to create Vulkan instance, {}.", rayon::current_thread_index().unwrap() , error);
-                    panic!();
-                }
-            }
-        }
+    return match entry.create_instance(&instance_create_info, None){
+        Ok(instance) => {Ok(instance)}
+        Err(ash::vk::Result::ERROR_LAYER_NOT_PRESENT) => {create_instance(entry, app_name.to_str().unwrap(), app_version, api_version, false, window, extensions)}
+        Err(error) => {Err(RenderError::from_vk(error, RenderError::Instance))}
     }
 }
\ No newline at end of file
@@ -2,4 +2,5 @@ pub mod instance;
 pub mod device;
 pub mod swapchain;
 pub mod render_pass;
-pub mod framebuffer;
\ No newline at end of file
+pub mod framebuffer;
+pub mod command;
\ No newline at end of file
@@ -1,8 +1,8 @@
 use ash::Device;
 use ash::vk::{AccessFlags, AttachmentDescription, AttachmentDescriptionFlags, AttachmentLoadOp, AttachmentReference, AttachmentStoreOp, DependencyFlags, Format, ImageLayout, PipelineBindPoint, PipelineStageFlags, RenderPass, RenderPassCreateFlags, RenderPassCreateInfo, SampleCountFlags, StructureType, SubpassDependency, SubpassDescription, SubpassDescriptionFlags};
-use slog::{crit, Logger};
+use crate::error::RenderError;
 
-pub unsafe fn create_render_pass(logger : &Logger, device : &Device, format : Format, depth_format : Format) -> RenderPass{
+pub unsafe fn create_render_pass(device : &Device, format : Format, depth_format : Format) -> Result<RenderPass, RenderError>{
     let attachments = [
         //Swapchain image
         AttachmentDescription{
@@ -74,11 +74,5 @@ pub unsafe fn create_render_pass(logger : &Logger, device : &Device, format : Fo
         p_dependencies : subpass_dependencies.as_ptr(),
 
     };
-    return match device.create_render_pass(&render_pass_create_info, None){
-        Ok(render_pass) => {render_pass}
-        Err(error) => {
-            crit!(logger, "[thread#{}]Failed to create render pass, {}.", rayon::current_thread_index().unwrap(), error);
-            panic!();
-        }
-    }
+    return device.create_render_pass(&render_pass_create_info, None).map_err(|error| RenderError::from_vk(error, RenderError::RenderPass));
 }
\ No newline at end of file
@@ -1,7 +1,7 @@
 use ash::extensions::khr::{Surface, Swapchain};
 use ash::Instance;
 use ash::vk::{ColorSpaceKHR, CompositeAlphaFlagsKHR, Extent2D, Format, FormatFeatureFlags, ImageUsageFlags, PhysicalDevice, PresentModeKHR, SharingMode, StructureType, SurfaceFormatKHR, SurfaceKHR, SurfaceTransformFlagsKHR, SwapchainCreateFlagsKHR, SwapchainCreateInfoKHR, SwapchainKHR};
-use slog::{crit, Logger};
+use crate::error::RenderError;
 
 pub const FORMATS : [SurfaceFormatKHR;2] = [
     SurfaceFormatKHR{format:Format::R8G8B8A8_SRGB, color_space : ColorSpaceKHR::SRGB_NONLINEAR},
@@ -24,10 +24,10 @@ pub struct SwapchainInfo{
     min_image_count : u32,
 }
 impl SwapchainInfo{
-    pub unsafe fn new(logger : &Logger, instance : &Instance, surface_loader : &Surface, surface : SurfaceKHR, device : PhysicalDevice, stencil_buffering : bool) -> Self{
-        let supported_formats = surface_loader.get_physical_device_surface_formats(device, surface).unwrap();
-        let supported_present_modes = surface_loader.get_physical_device_surface_present_modes(device, surface).unwrap();
-        let capabilities = surface_loader.get_physical_device_surface_capabilities(device, surface).unwrap();
+    pub unsafe fn new(instance : &Instance, surface_loader : &Surface, surface : SurfaceKHR, device : PhysicalDevice, stencil_buffering : bool) -> Result<Self, RenderError>{
+        let supported_formats = surface_loader.get_physical_device_surface_formats(device, surface).map_err(|error| RenderError::from_vk(error, RenderError::Surface))?;
+        let supported_present_modes = surface_loader.get_physical_device_surface_present_modes(device, surface).map_err(|error| RenderError::from_vk(error, RenderError::Surface))?;
+        let capabilities = surface_loader.get_physical_device_surface_capabilities(device, surface).map_err(|error| RenderError::from_vk(error, RenderError::Surface))?;
         let mut format = None;
         let mut depth_format = None;
         for srgb_format in FORMATS{
@@ -43,14 +43,11 @@ impl SwapchainInfo{
         }
         let format = match format{
             Some(format) => {format}
-            None => {supported_formats[0]}
+            None => {*supported_formats.first().ok_or(RenderError::Surface(ash::vk::Result::ERROR_FORMAT_NOT_SUPPORTED))?}
         };
-        let depth_format = match depth_format{
-            Some(depth_format) => {depth_format}
-            None => {crit!(logger, "[thread#{}]Failed to get supported depth format.", rayon::current_thread_index().unwrap());panic!()}
-        };
-        let min_image_count = if capabilities.min_image_count + 1 <= capabilities.max_image_count || capabilities.max_image_count == 0 {capabilities.min_image_count + 1} else {capabilities.max_image_count};
-        return Self{
+        let depth_format = depth_format.ok_or(RenderError::DepthFormat)?;
+        let min_image_count = if capabilities.min_image_count < capabilities.max_image_count || capabilities.max_image_count == 0 {capabilities.min_image_count + 1} else {capabilities.max_image_count};
+        return Ok(Self{
             extent : if capabilities.current_extent.width!=u32::MAX{capabilities.current_extent}else{Extent2D{width:0,height:0}},
             present_mode : if supported_present_modes.contains(&PresentModeKHR::MAILBOX){PresentModeKHR::MAILBOX}else{PresentModeKHR::FIFO},
             transform : capabilities.current_transform,
@@ -58,10 +55,10 @@ impl SwapchainInfo{
             color_space : format.color_space,
             depth_format,
             min_image_count,
-        }
+        })
     }
 }
-pub unsafe fn create_swapchain(logger : &Logger, loader : &Swapchain, info : &SwapchainInfo, surface : SurfaceKHR) -> SwapchainKHR{
+pub unsafe fn create_swapchain(loader : &Swapchain, info : &SwapchainInfo, surface : SurfaceKHR) -> Result<SwapchainKHR, RenderError>{
     let swapchain_create_info = SwapchainCreateInfoKHR{
         s_type : StructureType::SWAPCHAIN_CREATE_INFO_KHR,
         p_next : std::ptr::null(),
@@ -82,11 +79,5 @@ pub unsafe fn create_swapchain(logger : &Logger, loader : &Swapchain, info : &Sw
         p_queue_family_indices : std::ptr::null(),
         old_swapchain : SwapchainKHR::null(),
     };
-    return match loader.create_swapchain(&swapchain_create_info, None){
-        Ok(swapchain) => {swapchain}
-        Err(error) => {
-            crit!(logger, "[thread#{}]Failed to create swapchain, {}.", rayon::current_thread_index().unwrap(), error);
-            panic!();
-        }
-    }
+    return loader.create_swapchain(&swapchain_create_info, None).map_err(|error| RenderError::from_vk(error, RenderError::Swapchain));
 }
\ No newline at end of file
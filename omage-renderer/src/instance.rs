@@ -1,8 +1,12 @@
 use ash::{Entry, Instance};
+use ash::extensions::khr::GetPhysicalDeviceProperties2;
 use ash::vk::SurfaceKHR;
-use slog::{crit, Logger};
+use slog::{info, Logger};
 use winit::window::Window;
 use serde_derive::{Serialize, Deserialize};
+use omage_util::config::{Value, Versioned};
+use crate::capabilities::{ApiVersion, DeviceRequirements};
+use crate::error::RenderError;
 
 pub struct RenderInstance{
     pub config : RenderConfig,
@@ -10,30 +14,97 @@ pub struct RenderInstance{
     pub entry : Entry,
     pub instance : Instance,
     pub surface : SurfaceKHR,
+    pub api_version : ApiVersion,
+    pub properties2 : Option<GetPhysicalDeviceProperties2>,
+    pub requirements : DeviceRequirements,
 }
 impl RenderInstance{
-    pub unsafe fn new(logger : Logger, window : &Window, config : RenderConfig) -> Self{
-        let entry = match Entry::load(){Ok(entry)=>{entry}Err(error)=>{crit!(logger, "Failed to load Vulkan driver, {}.",error);panic!()}};
-        let instance = crate::functions::instance::create_instance(&logger, &entry, config.debugging, window).unwrap();
+    ///The app name and major.minor.patch version are reported to the driver.
+    pub unsafe fn new(logger : Logger, app_name : &str, app_version : &str, window : &Window, config : RenderConfig, requirements : DeviceRequirements) -> Result<Self, RenderError>{
+        let entry = Entry::load().map_err(|error| RenderError::DriverLoad(error.to_string()))?;
+        let api_version = crate::functions::instance::get_instance_version(&entry).min(config.max_api_version);
+        info!(logger, "Using Vulkan instance version {:?}.", api_version);
+        //Before 1.1 the extended feature queries are only available through an extension.
+        let properties2_supported = api_version == ApiVersion::V1_0 && crate::functions::instance::supports_instance_extension(&entry, GetPhysicalDeviceProperties2::name());
+        let instance_extensions = if properties2_supported{vec![GetPhysicalDeviceProperties2::name()]}else{vec![]};
+        let instance = crate::functions::instance::create_instance(&entry, app_name, app_version, api_version, config.validation, window, &instance_extensions)?;
+        let properties2 = if properties2_supported{Some(GetPhysicalDeviceProperties2::new(&entry, &instance))}else{None};
         let surface = match ash_window::create_surface(&entry, &instance, &window, None){
             Ok(surface) => {surface}
-            Err(error) => {crit!(logger, "[thread#{}]Failed to create Vulkan surface, {}.", rayon::current_thread_index().unwrap(), error);panic!()}
+            Err(error) => {
+                instance.destroy_instance(None);
+                return Err(RenderError::from_vk(error, RenderError::Surface));
+            }
         };
-        return Self{
-            logger,entry,config,instance,surface,
-        }
+        return Ok(Self{
+            logger,entry,config,instance,surface,api_version,properties2,requirements,
+        })
     }
 }
 #[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct RenderConfig{
-    pub debugging : bool,
+    ///Enables the Khronos validation layer when it is installed.
+    pub validation : bool,
     pub gpu : String,
+    pub max_api_version : ApiVersion,
 }
 impl Default for RenderConfig{
     fn default() -> Self {
         return Self{
-            debugging : false,
+            validation : false,
             gpu : String::new(),
+            max_api_version : ApiVersion::V1_3,
+        }
+    }
+}
+impl Versioned for RenderConfig{
+    const VERSION : u32 = 2;
+    fn migrate(version : u32, value : &mut Value) -> Result<(), String>{
+        let table = value.as_object_mut().ok_or("the file is not a table")?;
+        return match version{
+            //Version 2 renamed `debugging` after the validation layer it turns on.
+            1 => {
+                if let Some(debugging) = table.remove("debugging"){table.insert("validation".to_owned(), debugging);}
+                Ok(())
+            }
+            _ => {Err(format!("no migration from version {}", version))}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use omage_util::config::{parse, serialize, Format};
+    use super::*;
+
+    #[test]
+    fn debugging_is_migrated_to_validation(){
+        let files = [
+            (Format::Toml, "debugging = true\ngpu = \"gpu\"\nmax_api_version = \"1.1\"\n"),
+            (Format::Toml, "# omage file version = 1, hash = 0\ndebugging = true\ngpu = \"gpu\"\nmax_api_version = \"1.1\"\n"),
+            (Format::Json, "{\"debugging\": true, \"gpu\": \"gpu\", \"max_api_version\": \"1.1\"}"),
+        ];
+        for (format, data) in files{
+            let parsed = parse::<RenderConfig>(data.as_bytes(), format).unwrap();
+            assert!(parsed.file.validation, "{}", data);
+            assert_eq!(parsed.file.gpu, "gpu", "{}", data);
+            assert_eq!(parsed.file.max_api_version, ApiVersion::V1_1, "{}", data);
         }
     }
-}
\ No newline at end of file
+    #[test]
+    fn old_ron_files_keep_their_api_version(){
+        let parsed = parse::<RenderConfig>(b"(debugging: true, gpu: \"gpu\", max_api_version: r#1.1)", Format::Ron).unwrap();
+        assert!(parsed.file.validation);
+        assert_eq!(parsed.file.gpu, "gpu");
+        assert_eq!(parsed.file.max_api_version, ApiVersion::V1_1);
+    }
+    #[test]
+    fn the_api_version_round_trips(){
+        for format in [Format::Toml, Format::Ron, Format::Json, Format::Binary]{
+            let config = RenderConfig{max_api_version : ApiVersion::V1_1, ..Default::default()};
+            let data = serialize(&config, format).unwrap();
+            assert_eq!(parse::<RenderConfig>(&data, format).unwrap().file.max_api_version, ApiVersion::V1_1, "{:?}", format);
+        }
+    }
+}
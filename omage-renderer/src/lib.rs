@@ -1,39 +1,117 @@
-use ash::{Device, Entry, Instance};
-use ash::extensions::khr::{Surface, Swapchain};
-use ash::vk::{SurfaceKHR, SwapchainKHR, ImageView, RenderPass, Framebuffer};
-use crossbeam_channel::{Receiver, Sender};
-use objects::image::AllocatedImageView;
-use slog::{info, Logger};
-use omage_util::{FileType, PathManager};
-use crate::allocator::Allocator;
-use crate::functions::swapchain::SwapchainInfo;
+#![allow(clippy::needless_return, clippy::missing_safety_doc)]
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use ash::{Entry, Instance};
+use ash::extensions::khr::{GetPhysicalDeviceProperties2, Surface};
+use ash::vk::SurfaceKHR;
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, TryRecvError};
+use objects::buffer::BufferDescription;
+use objects::image::{AllocatedImageView, ImageDescription};
+use objects::pipeline::{ComputeDispatch, ComputePipeline, ComputePipelineDescription};
+use objects::upload::GpuAsset;
+use slog::{info, o, warn, Logger};
+use omage_util::{panic_message, FileType, PathManager};
+use crate::capabilities::{ApiVersion, DeviceCapabilities, DeviceRequirements};
+use crate::context::{ContextInfo, DeviceContext, Resources};
+use crate::error::RenderError;
 use crate::instance::{RenderConfig, RenderInstance};
+use crate::scene::RenderScene;
 
 pub mod instance;
+pub mod capabilities;
+pub mod error;
 mod functions;
+mod context;
 mod allocator;
 pub mod objects;
+pub mod scene;
 
+const RECOVERY_INTERVAL : Duration = Duration::from_secs(1);
 
 pub struct Renderer{
     sender : Sender<RenderTask>,
     receiver : Receiver<RenderResult>,
+    capabilities : DeviceCapabilities,
 }
 impl Renderer{
-    pub fn new(instance : RenderInstance, path_manager : PathManager) -> Self{
+    pub fn new(instance : RenderInstance, path_manager : PathManager) -> Result<Self, RenderError>{
         let (sender, thread_receiver) = crossbeam_channel::bounded(2);
-        let (thread_sender, receiver) = crossbeam_channel::bounded(2);
-        rayon::spawn(|| unsafe {
-            let renderer = RenderThread::new(instance, path_manager, thread_sender, thread_receiver);
-            renderer.listen();
+        //Unbounded so notifications like a device loss are never dropped while the engine is busy.
+        let (thread_sender, receiver) = crossbeam_channel::unbounded();
+        rayon::spawn(|| {
+            let error_sender = thread_sender.clone();
+            //A panic would abort the process on the rayon pool, it is turned into a failure for the engine instead.
+            let result = std::panic::catch_unwind(AssertUnwindSafe(|| unsafe {
+                match RenderThread::new(instance, path_manager, thread_sender, thread_receiver){
+                    Ok(renderer) => {renderer.listen()}
+                    Err(error) => {let _ = error_sender.send(RenderResult::Failed(error));}
+                }
+            }));
+            if let Err(payload) = result{let _ = error_sender.send(RenderResult::Failed(RenderError::Panicked(panic_message(payload.as_ref()))));}
         });
-        return Self{
-            sender, receiver,
+        return match receiver.recv(){
+            Ok(RenderResult::Initialized(capabilities)) => {Ok(Self{sender, receiver, capabilities})}
+            Ok(RenderResult::Failed(error)) => {Err(error)}
+            _ => {Err(RenderError::Disconnected)}
         }
     }
+    ///The optional device features and extensions that were actually enabled, updated when `poll` returns a recovered device.
+    pub fn capabilities(&self) -> &DeviceCapabilities{
+        return &self.capabilities;
+    }
+    ///Hands the scene of the frame to the render thread, dropped when the render thread is still busy with older ones.
+    pub fn submit(&self, scene : RenderScene){
+        let _ = self.sender.try_send(RenderTask::Submit(scene));
+    }
+    ///Uploads the asset under the id, replacing what was uploaded under it before.
+    pub fn upload(&self, id : u64, asset : Arc<GpuAsset>){
+        let _ = self.sender.send(RenderTask::Upload(id, asset));
+    }
+    pub fn release(&self, id : u64){
+        let _ = self.sender.send(RenderTask::Release(id));
+    }
+    ///Registers an image that survives device losses and returns its index.
+    pub fn register_image(&self, description : ImageDescription) -> Result<usize, RenderError>{
+        return self.request(|reply| RenderTask::RegisterImage(description, reply));
+    }
+    ///Registers a compute pipeline and returns the index dispatches refer to it by.
+    pub fn register_compute_pipeline(&self, description : ComputePipelineDescription) -> Result<usize, RenderError>{
+        return self.request(|reply| RenderTask::RegisterComputePipeline(description, reply));
+    }
+    ///Creates a buffer shared between the graphics and the compute queue and returns its id.
+    pub fn create_buffer(&self, description : BufferDescription) -> Result<u64, RenderError>{
+        return self.request(|reply| RenderTask::CreateBuffer(description, reply));
+    }
+    pub fn destroy_buffer(&self, id : u64){
+        let _ = self.sender.send(RenderTask::DestroyBuffer(id));
+    }
+    ///Points a buffer binding of a compute pipeline at the buffer with the id.
+    pub fn bind_compute_buffer(&self, pipeline : usize, binding : u32, buffer : u64) -> Result<(), RenderError>{
+        return self.request(|reply| RenderTask::BindComputeBuffer(pipeline, binding, buffer, reply));
+    }
+    ///Queues the dispatches for the async compute queue, errors arrive as `RenderResult::Error`.
+    pub fn dispatch_compute(&self, dispatches : Vec<ComputeDispatch>){
+        let _ = self.sender.send(RenderTask::DispatchCompute(dispatches));
+    }
+    ///Sends a task carrying a reply channel and waits for the render thread to answer it.
+    fn request<T>(&self, task : impl FnOnce(Sender<Result<T, RenderError>>) -> RenderTask) -> Result<T, RenderError>{
+        let (reply, result) = crossbeam_channel::bounded(1);
+        self.sender.send(task(reply)).map_err(|_| RenderError::Disconnected)?;
+        return result.recv().unwrap_or(Err(RenderError::Disconnected));
+    }
+    ///Returns the next notification from the render thread, if any.
+    pub fn poll(&mut self) -> Option<RenderResult>{
+        let result = self.receiver.try_recv().ok();
+        if let Some(RenderResult::DeviceRecovered(capabilities)) = &result{self.capabilities = capabilities.clone()}
+        return result;
+    }
+    ///Stops the render thread and waits for it, returns right away if it already stopped.
     pub fn stop(&self){
-        self.sender.send(RenderTask::Stop).unwrap();
-        while self.receiver.recv().unwrap()!=RenderResult::Stopped{};
+        let _ = self.sender.send(RenderTask::Stop);
+        while let Ok(result) = self.receiver.recv(){
+            if result == RenderResult::Stopped{break;}
+        }
     }
 }
 
@@ -47,90 +125,237 @@ pub struct RenderThread{
     instance : Instance,
     surface_loader : Surface,
     surface : SurfaceKHR,
-    device : Device,
-    swapchain_loader : Swapchain,
-    swapchain : SwapchainKHR,
-    allocator : Allocator,
-    swapchain_image_views : Vec<ImageView>,
-    depth_image : AllocatedImageView,
-    render_pass : RenderPass,
-    framebuffers : Vec<Framebuffer>,
+    api_version : ApiVersion,
+    properties2 : Option<GetPhysicalDeviceProperties2>,
+    requirements : DeviceRequirements,
+    resources : Resources,
+    ///The id handed out for the next buffer.
+    next_buffer : u64,
+    context : Option<DeviceContext>,
+    ///When the next attempt to recreate a lost device is due.
+    next_recovery : Instant,
+    ///The latest scene submitted by the engine.
+    scene : RenderScene,
 }
 impl RenderThread{
-    pub unsafe fn new(instance : RenderInstance, path_manager : PathManager, sender : Sender<RenderResult>, receiver : Receiver<RenderTask>) -> Self{
-        let logger = instance.logger;
-        let config = instance.config;
-        let surface = instance.surface;
-        let entry = instance.entry;
-        let instance = instance.instance;
-        let surface_loader = Surface::new(&entry, &instance);
-        let physical_devices = functions::device::get_compatible_devices(&logger, &instance, &surface_loader, surface);
-        let physical_device = functions::device::select_physical_device(&logger, &instance, physical_devices, &config);
-        let queue_info = functions::device::QueueInfo::new(&logger, &instance, physical_device);
-        let device = functions::device::create_device(&logger, &instance, &queue_info, physical_device, false, false);
-        let swapchain_loader = Swapchain::new(&instance, &device);
-        let swapchain_info = SwapchainInfo::new(&logger, &instance, &surface_loader, surface, physical_device, false);
-        let swapchain = functions::swapchain::create_swapchain(&logger, &swapchain_loader, &swapchain_info, surface);
-        let swapchain_images = swapchain_loader.get_swapchain_images(swapchain).unwrap();
-        let swapchain_image_views = objects::image::create_swapchain_image_views(&device, swapchain_info.format, &swapchain_images);
-        let mut allocator = Allocator::new(&logger, &instance, physical_device, &device);
-        let depth_image = objects::image::AllocatedImageView::new_depth(&logger, &mut allocator, swapchain_info.extent, swapchain_info.depth_format);
-        let render_pass = functions::render_pass::create_render_pass(&logger, &device, swapchain_info.format, swapchain_info.depth_format);
-        let framebuffers = functions::framebuffer::create_framebuffers(&logger, &device, render_pass, &swapchain_image_views, depth_image.view, swapchain_info.extent);
-        info!(logger, "[thread#{}]Successfully created the main renderer.", rayon::current_thread_index().unwrap());
-        return Self{
-            logger,config,surface,surface_loader,_entry:entry,instance,path_manager,sender,receiver,device,swapchain_loader,swapchain,allocator,swapchain_image_views,depth_image,render_pass,framebuffers,
-        }
+    pub unsafe fn new(instance : RenderInstance, path_manager : PathManager, sender : Sender<RenderResult>, receiver : Receiver<RenderTask>) -> Result<Self, RenderError>{
+        let surface_loader = Surface::new(&instance.entry, &instance.instance);
+        let mut renderer = Self{
+            logger : instance.logger.new(o!("thread" => rayon::current_thread_index())),
+            _entry : instance.entry,
+            config : instance.config,
+            instance : instance.instance,
+            surface : instance.surface,
+            api_version : instance.api_version,
+            properties2 : instance.properties2,
+            requirements : instance.requirements,
+            resources : Resources::default(),
+            next_buffer : 0,
+            context : None,
+            next_recovery : Instant::now(),
+            scene : RenderScene::default(),
+            surface_loader,path_manager,sender,receiver,
+        };
+        renderer.context = Some(renderer.create_context()?);
+        info!(renderer.logger, "Successfully created the main renderer.");
+        let capabilities = renderer.context.as_ref().unwrap().capabilities.clone();
+        renderer.sender.send(RenderResult::Initialized(capabilities)).unwrap();
+        return Ok(renderer);
+    }
+    unsafe fn create_context(&self) -> Result<DeviceContext, RenderError>{
+        let info = ContextInfo{
+            logger : &self.logger,
+            instance : &self.instance,
+            api_version : self.api_version,
+            properties2 : self.properties2.as_ref(),
+            surface_loader : &self.surface_loader,
+            surface : self.surface,
+            requirements : &self.requirements,
+            config : &self.config,
+        };
+        return DeviceContext::new(&info, &self.resources);
     }
     pub unsafe fn listen(mut self){
-        let mut task = self.receiver.try_recv();
-        while task != Ok(RenderTask::Stop) {
-            if task.is_ok() {
-                //let task = task.unwrap();
+        loop {
+            loop {
+                let task = match self.recovery_delay(){
+                    //Without a device there is nothing to draw, so tasks are awaited until the next recovery attempt is due.
+                    Some(delay) => {
+                        match self.receiver.recv_timeout(delay){
+                            Ok(task) => {task}
+                            Err(RecvTimeoutError::Timeout) => {break;}
+                            Err(RecvTimeoutError::Disconnected) => {drop(self); return;}
+                        }
+                    }
+                    None => {
+                        match self.receiver.try_recv(){
+                            Ok(task) => {task}
+                            Err(TryRecvError::Empty) => {break;}
+                            //The engine thread is gone without stopping the renderer, for example after it panicked.
+                            Err(TryRecvError::Disconnected) => {drop(self); return;}
+                        }
+                    }
+                };
+                match task{
+                    RenderTask::Stop => {drop(self); return;}
+                    RenderTask::Submit(scene) => {self.scene = scene}
+                    RenderTask::Upload(id, asset) => {
+                        let result = self.upload(id, asset);
+                        self.report(result);
+                    }
+                    RenderTask::Release(id) => {self.release(id)}
+                    RenderTask::RegisterImage(description, reply) => {let _ = reply.send(self.register_image(description));}
+                    RenderTask::RegisterComputePipeline(description, reply) => {let _ = reply.send(self.register_compute_pipeline(description));}
+                    RenderTask::CreateBuffer(description, reply) => {let _ = reply.send(self.create_buffer(description));}
+                    RenderTask::DestroyBuffer(id) => {self.destroy_buffer(id)}
+                    RenderTask::BindComputeBuffer(pipeline, binding, buffer, reply) => {let _ = reply.send(self.bind_compute_buffer(pipeline, binding, buffer));}
+                    RenderTask::DispatchCompute(dispatches) => {
+                        let result = self.dispatch_compute(&dispatches);
+                        self.report(result);
+                    }
+                }
+            }
+            if self.context.is_none(){
+                self.recover();
+                continue;
             }
-            self.draw();
-            task = self.receiver.try_recv();
+            let result = self.draw();
+            self.report(result);
         }
-        drop(self);
     }
-    pub fn draw(&mut self){
-
+    ///How long to wait for tasks before the next recovery attempt, None while the device is alive.
+    fn recovery_delay(&self) -> Option<Duration>{
+        return match self.context{
+            Some(_) => {None}
+            None => {Some(self.next_recovery.saturating_duration_since(Instant::now()))}
+        }
     }
-    unsafe fn destroy_swapchain(&mut self){
-        for &framebuffer in self.framebuffers.iter(){
-            self.device.destroy_framebuffer(framebuffer, None);
+    ///Tears down the lost device and tells the engine, recovery is attempted by the task loop.
+    unsafe fn report(&mut self, result : Result<(), RenderError>){
+        match result{
+            Err(RenderError::DeviceLost) => {
+                if let Some(mut context) = self.context.take(){
+                    warn!(self.logger, "The Vulkan device was lost, recreating the renderer.");
+                    context.destroy();
+                    self.next_recovery = Instant::now();
+                    let _ = self.sender.send(RenderResult::DeviceLost);
+                }
+            }
+            Err(error) => {
+                warn!(self.logger, "Render error, {}.", error);
+                let _ = self.sender.send(RenderResult::Error(error));
+            }
+            Ok(()) => {}
         }
-        self.depth_image.destroy(&mut self.allocator);
-        for &swapchain_image_view in self.swapchain_image_views.iter(){
-            self.device.destroy_image_view(swapchain_image_view, None);
+    }
+    ///Uploads the asset and keeps its data, so it is uploaded again after a device loss.
+    pub unsafe fn upload(&mut self, id : u64, asset : Arc<GpuAsset>) -> Result<(), RenderError>{
+        self.resources.uploads.insert(id, asset.clone());
+        let context = self.context.as_mut().ok_or(RenderError::DeviceLost)?;
+        return context.upload(id, &asset);
+    }
+    pub unsafe fn release(&mut self, id : u64){
+        self.resources.uploads.remove(&id);
+        if let Some(context) = self.context.as_mut(){context.release(id)}
+    }
+    pub fn draw(&mut self) -> Result<(), RenderError>{
+        let context = self.context.as_mut().ok_or(RenderError::DeviceLost)?;
+        return unsafe{context.submit_frame()};
+    }
+    ///Registers an image that is recreated from its description whenever the device is rebuilt.
+    pub unsafe fn register_image(&mut self, description : ImageDescription) -> Result<usize, RenderError>{
+        let context = self.context.as_mut().ok_or(RenderError::DeviceLost)?;
+        let image = AllocatedImageView::from_description(&mut context.allocator, &description)?;
+        context.images.push(image);
+        self.resources.images.push(description);
+        return Ok(self.resources.images.len() - 1);
+    }
+    ///Registers a compute pipeline that is recreated from its description whenever the device is rebuilt.
+    pub unsafe fn register_compute_pipeline(&mut self, description : ComputePipelineDescription) -> Result<usize, RenderError>{
+        let context = self.context.as_mut().ok_or(RenderError::DeviceLost)?;
+        context.compute_pipelines.push(ComputePipeline::new(&context.device, &description)?);
+        self.resources.compute_pipelines.push(description);
+        return Ok(self.resources.compute_pipelines.len() - 1);
+    }
+    ///Creates a buffer usable from both the graphics and the compute queue and returns its id, it is recreated whenever the device is rebuilt.
+    pub unsafe fn create_buffer(&mut self, description : BufferDescription) -> Result<u64, RenderError>{
+        let context = self.context.as_mut().ok_or(RenderError::DeviceLost)?;
+        let id = self.next_buffer;
+        context.create_buffer(id, &description)?;
+        self.next_buffer += 1;
+        self.resources.buffers.insert(id, description);
+        return Ok(id);
+    }
+    ///Destroys the buffer and forgets the bindings pointing at it.
+    pub unsafe fn destroy_buffer(&mut self, id : u64){
+        self.resources.buffers.remove(&id);
+        self.resources.compute_bindings.retain(|_, buffer| *buffer != id);
+        if let Some(context) = self.context.as_mut(){context.destroy_buffer(id)}
+    }
+    pub unsafe fn bind_compute_buffer(&mut self, pipeline : usize, binding : u32, buffer : u64) -> Result<(), RenderError>{
+        let context = self.context.as_mut().ok_or(RenderError::DeviceLost)?;
+        context.bind_compute_buffer(pipeline, binding, buffer)?;
+        self.resources.compute_bindings.insert((pipeline, binding), buffer);
+        return Ok(());
+    }
+    ///Submits the dispatches on the async compute queue when the device has one, otherwise on the graphics queue family.
+    pub unsafe fn dispatch_compute(&mut self, dispatches : &[ComputeDispatch]) -> Result<(), RenderError>{
+        let context = self.context.as_mut().ok_or(RenderError::DeviceLost)?;
+        return context.dispatch_compute(dispatches);
+    }
+    ///Rebuilds the lost device from the CPU side state, failed attempts are retried after `RECOVERY_INTERVAL`.
+    unsafe fn recover(&mut self){
+        match self.create_context(){
+            Ok(context) => {
+                info!(self.logger, "Recreated the renderer after a device loss.");
+                let _ = self.sender.send(RenderResult::DeviceRecovered(context.capabilities.clone()));
+                self.context = Some(context);
+            }
+            Err(error) => {
+                warn!(self.logger, "Failed to recreate the renderer, {}, retrying.", error);
+                self.next_recovery = Instant::now() + RECOVERY_INTERVAL;
+            }
         }
-        self.swapchain_image_views=vec!();
-        self.swapchain_loader.destroy_swapchain(self.swapchain, None);
     }
 }
 impl Drop for RenderThread{
     fn drop(&mut self) {
-        self.path_manager.save_file("render", FileType::Config, &self.config);
-        unsafe{self.device.device_wait_idle()}.unwrap();
-        info!(self.logger, "[thread#{}]Destroying the renderer.",rayon::current_thread_index().unwrap());
+        if let Err(error) = self.path_manager.save_file("render", FileType::Config, &self.config){warn!(self.logger, "Failed to save the render config, {}.", error)}
+        info!(self.logger, "Destroying the renderer.");
 
         unsafe {
-            self.destroy_swapchain();
-            self.allocator.destroy();
-            self.device.destroy_render_pass(self.render_pass, None);
-            self.device.destroy_device(None);
+            if let Some(mut context) = self.context.take(){context.destroy()}
             self.surface_loader.destroy_surface(self.surface, None);
             self.instance.destroy_instance(None);
         }
-        self.sender.send(RenderResult::Stopped).unwrap();
+        let _ = self.sender.send(RenderResult::Stopped);
     }
 }
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Clone)]
 pub enum RenderTask{
+    Submit(RenderScene),
+    Upload(u64, Arc<GpuAsset>),
+    Release(u64),
+    ///Answered with the index of the image.
+    RegisterImage(ImageDescription, Sender<Result<usize, RenderError>>),
+    ///Answered with the index of the pipeline.
+    RegisterComputePipeline(ComputePipelineDescription, Sender<Result<usize, RenderError>>),
+    ///Answered with the id of the buffer.
+    CreateBuffer(BufferDescription, Sender<Result<u64, RenderError>>),
+    DestroyBuffer(u64),
+    ///Binds the buffer with the id to a binding of a compute pipeline.
+    BindComputeBuffer(usize, u32, u64, Sender<Result<(), RenderError>>),
+    DispatchCompute(Vec<ComputeDispatch>),
     Stop,
 }
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Clone, PartialEq)]
 pub enum RenderResult{
+    Initialized(DeviceCapabilities),
+    Failed(RenderError),
+    ///A non fatal error while drawing.
+    Error(RenderError),
+    DeviceLost,
+    DeviceRecovered(DeviceCapabilities),
     Success,
     Stopped,
 }
\ No newline at end of file
@@ -0,0 +1,49 @@
+use ash::vk::{Buffer, BufferCreateFlags, BufferCreateInfo, BufferUsageFlags, MemoryPropertyFlags, SharingMode, StructureType};
+use crate::allocator::{allocation::Allocation, Allocator};
+use crate::error::RenderError;
+
+///The CPU side description of a buffer created through the renderer.
+#[derive(Copy, Clone, Debug)]
+pub struct BufferDescription{
+    pub size : u64,
+    pub usage : BufferUsageFlags,
+    pub flags : MemoryPropertyFlags,
+}
+pub struct AllocatedBuffer{
+    pub buffer : Buffer,
+    pub allocation : Allocation,
+    pub size : u64,
+}
+impl AllocatedBuffer{
+    ///Buffers used by more than one queue family are created concurrent, so no ownership transfers are needed.
+    pub unsafe fn new(allocator : &mut Allocator, size : u64, usage : BufferUsageFlags, flags : MemoryPropertyFlags, queue_families : &[u32]) -> Result<Self, RenderError>{
+        let mut families = queue_families.to_vec();
+        families.sort_unstable();
+        families.dedup();
+        let buffer_create_info = BufferCreateInfo{
+            s_type : StructureType::BUFFER_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : BufferCreateFlags::empty(),
+            size,
+            usage,
+            sharing_mode : if families.len() > 1{SharingMode::CONCURRENT}else{SharingMode::EXCLUSIVE},
+            queue_family_index_count : if families.len() > 1{families.len() as u32}else{0},
+            p_queue_family_indices : if families.len() > 1{families.as_ptr()}else{std::ptr::null()},
+        };
+        let buffer = allocator.device.create_buffer(&buffer_create_info, None).map_err(|error| RenderError::from_vk(error, RenderError::Buffer))?;
+        let allocation = match allocator.allocate_buffer_memory(buffer, flags){
+            Ok(allocation) => {allocation}
+            Err(error) => {
+                allocator.device.destroy_buffer(buffer, None);
+                return Err(error);
+            }
+        };
+        return Ok(Self{
+            buffer,allocation,size,
+        })
+    }
+    pub unsafe fn destroy(&self, allocator : &mut Allocator){
+        allocator.destroy_allocation(&self.allocation);
+        allocator.device.destroy_buffer(self.buffer, None);
+    }
+}
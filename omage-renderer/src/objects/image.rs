@@ -1,8 +1,7 @@
 use ash::{vk::{Image, StructureType, ImageCreateFlags, ImageCreateInfo, SharingMode, ImageUsageFlags, ImageTiling, Extent2D, Format, Extent3D, ImageType, ImageLayout, SampleCountFlags, MemoryPropertyFlags, ImageView, ImageViewCreateInfo, ImageViewCreateFlags, ComponentMapping, ComponentSwizzle, ImageViewType, ImageSubresourceRange, ImageAspectFlags}, Device};
-use slog::{Logger, crit};
-use omage_util::PathManager;
 
 use crate::allocator::{allocation::Allocation, Allocator};
+use crate::error::RenderError;
 
 pub struct AllocatedImage{
     pub image : Image,
@@ -12,8 +11,17 @@ pub struct AllocatedImageView{
     pub image : AllocatedImage,
     pub view : ImageView,
 }
+///The CPU side description of an image, used to recreate it after the device was lost.
+#[derive(Copy, Clone, Debug)]
+pub struct ImageDescription{
+    pub extent : Extent2D,
+    pub format : Format,
+    pub flags : MemoryPropertyFlags,
+    pub usage : ImageUsageFlags,
+    pub aspect : ImageAspectFlags,
+}
 impl AllocatedImage{
-    pub unsafe fn new_2d(logger : &Logger, allocator : &mut Allocator, extent : Extent2D, format : Format, flags : MemoryPropertyFlags, usage : ImageUsageFlags) -> Self{
+    pub unsafe fn new_2d(allocator : &mut Allocator, extent : Extent2D, format : Format, flags : MemoryPropertyFlags, usage : ImageUsageFlags) -> Result<Self, RenderError>{
         let image_create_info = ImageCreateInfo{
             s_type : StructureType::IMAGE_CREATE_INFO,
             p_next : std::ptr::null(),
@@ -31,18 +39,18 @@ impl AllocatedImage{
             mip_levels : 1,
             samples : SampleCountFlags::TYPE_1,
         };
-        let image = match allocator.device.create_image(&image_create_info, None){
-            Ok(image) => {image}
+        let image = allocator.device.create_image(&image_create_info, None).map_err(|error| RenderError::from_vk(error, RenderError::Image))?;
+        let allocation = match allocator.allocate_image_memory(image, flags){
+            Ok(allocation) => {allocation}
             Err(error) => {
-                crit!(logger, "[thread#{}]Failed to create Vulkan image, {}.",rayon::current_thread_index().unwrap(),error);
-                panic!();
+                allocator.device.destroy_image(image, None);
+                return Err(error);
             }
         };
-        let allocation = allocator.allocate_image_memory(image, flags);
-        return Self{
+        return Ok(Self{
             allocation,
             image,
-        }
+        })
     }
     pub unsafe fn destroy(&self, allocator : &mut Allocator){
         allocator.destroy_allocation(&self.allocation);
@@ -50,8 +58,8 @@ impl AllocatedImage{
     }
 }
 impl AllocatedImageView{
-    pub unsafe fn new_2d(logger : &Logger, allocator : &mut Allocator, extent : Extent2D, format : Format, flags : MemoryPropertyFlags, usage : ImageUsageFlags, aspect : ImageAspectFlags) -> Self{
-        let image = AllocatedImage::new_2d(logger, allocator, extent, format, flags, usage);
+    pub unsafe fn new_2d(allocator : &mut Allocator, extent : Extent2D, format : Format, flags : MemoryPropertyFlags, usage : ImageUsageFlags, aspect : ImageAspectFlags) -> Result<Self, RenderError>{
+        let image = AllocatedImage::new_2d(allocator, extent, format, flags, usage)?;
         let image_view_create_info = ImageViewCreateInfo{
             s_type : StructureType::IMAGE_VIEW_CREATE_INFO,
             p_next : std::ptr::null(),
@@ -68,23 +76,29 @@ impl AllocatedImageView{
                 level_count : 1,
             }
         };
-        let view = allocator.device.create_image_view(&image_view_create_info, None).unwrap();
-        return Self{
+        let view = match allocator.device.create_image_view(&image_view_create_info, None){
+            Ok(view) => {view}
+            Err(error) => {
+                image.destroy(allocator);
+                return Err(RenderError::from_vk(error, RenderError::Image));
+            }
+        };
+        return Ok(Self{
             image,view
-        }
+        })
     }
     pub unsafe fn destroy(&self, allocator : &mut Allocator){
         allocator.device.destroy_image_view(self.view, None);
         self.image.destroy(allocator);
     }
-    pub unsafe fn new_depth(logger : &Logger, allocator : &mut Allocator, extent : Extent2D, format : Format) -> Self{
-        return Self::new_2d(logger, allocator, extent, format, MemoryPropertyFlags::DEVICE_LOCAL, ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT, ImageAspectFlags::DEPTH);
+    pub unsafe fn from_description(allocator : &mut Allocator, description : &ImageDescription) -> Result<Self, RenderError>{
+        return Self::new_2d(allocator, description.extent, description.format, description.flags, description.usage, description.aspect);
     }
-    pub unsafe fn new_font(logger : &Logger, allocator : &mut Allocator, path_manager : PathManager) -> Self{
-        todo!();
+    pub unsafe fn new_depth(allocator : &mut Allocator, extent : Extent2D, format : Format) -> Result<Self, RenderError>{
+        return Self::new_2d(allocator, extent, format, MemoryPropertyFlags::DEVICE_LOCAL, ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT, ImageAspectFlags::DEPTH);
     }
 }
-pub unsafe fn create_swapchain_image_views(device : &Device, format : Format, images : &Vec<Image>) -> Vec<ImageView>{
+pub unsafe fn create_swapchain_image_views(device : &Device, format : Format, images : &[Image]) -> Result<Vec<ImageView>, RenderError>{
     let mut views = vec!();
     for &image in images.iter(){
         let image_view_create_info = ImageViewCreateInfo{
@@ -103,7 +117,13 @@ pub unsafe fn create_swapchain_image_views(device : &Device, format : Format, im
                 level_count : 1,
             }
         };
-        views.push(device.create_image_view(&image_view_create_info, None).unwrap());
+        match device.create_image_view(&image_view_create_info, None){
+            Ok(view) => {views.push(view)}
+            Err(error) => {
+                for &view in views.iter(){device.destroy_image_view(view, None)}
+                return Err(RenderError::from_vk(error, RenderError::Image));
+            }
+        }
     }
-    return views;
+    return Ok(views);
 }
\ No newline at end of file
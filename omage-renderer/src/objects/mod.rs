@@ -1 +1,5 @@
 pub mod image;
+pub mod buffer;
+pub mod pipeline;
+
+pub mod upload;
\ No newline at end of file
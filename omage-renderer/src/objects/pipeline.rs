@@ -0,0 +1,166 @@
+use ash::Device;
+use ash::vk::{Buffer, ComputePipelineCreateInfo, DescriptorBufferInfo, DescriptorPool, DescriptorPoolCreateFlags, DescriptorPoolCreateInfo, DescriptorPoolSize, DescriptorSet, DescriptorSetAllocateInfo, DescriptorSetLayout, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateFlags, DescriptorSetLayoutCreateInfo, DescriptorType, Pipeline, PipelineCache, PipelineCreateFlags, PipelineLayout, PipelineLayoutCreateFlags, PipelineLayoutCreateInfo, PipelineShaderStageCreateFlags, PipelineShaderStageCreateInfo, PushConstantRange, ShaderModule, ShaderModuleCreateFlags, ShaderModuleCreateInfo, ShaderStageFlags, StructureType, WriteDescriptorSet};
+use crate::error::RenderError;
+
+///The CPU side description of a compute pipeline, used to recreate it after the device was lost.
+#[derive(Clone, Debug)]
+pub struct ComputePipelineDescription{
+    pub code : Vec<u32>,
+    pub bindings : Vec<DescriptorType>,
+    pub push_constant_size : u32,
+}
+#[derive(Clone, Debug)]
+pub struct ComputeDispatch{
+    pub pipeline : usize,
+    pub group_count : [u32; 3],
+    pub push_constants : Vec<u8>,
+}
+pub struct ComputePipeline{
+    pub pipeline : Pipeline,
+    pub layout : PipelineLayout,
+    pub set_layout : DescriptorSetLayout,
+    pub descriptor_pool : DescriptorPool,
+    pub descriptor_set : DescriptorSet,
+    pub bindings : Vec<DescriptorType>,
+}
+impl ComputePipeline{
+    pub unsafe fn new(device : &Device, description : &ComputePipelineDescription) -> Result<Self, RenderError>{
+        let shader_module = create_shader_module(device, &description.code)?;
+        let bindings = description.bindings.iter().enumerate().map(|(i, &descriptor_type)| DescriptorSetLayoutBinding{
+            binding : i as u32,
+            descriptor_type,
+            descriptor_count : 1,
+            stage_flags : ShaderStageFlags::COMPUTE,
+            p_immutable_samplers : std::ptr::null(),
+        }).collect::<Vec<_>>();
+        let set_layout_create_info = DescriptorSetLayoutCreateInfo{
+            s_type : StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : DescriptorSetLayoutCreateFlags::empty(),
+            binding_count : bindings.len() as u32,
+            p_bindings : bindings.as_ptr(),
+        };
+        let set_layout = match device.create_descriptor_set_layout(&set_layout_create_info, None){
+            Ok(set_layout) => {set_layout}
+            Err(error) => {
+                device.destroy_shader_module(shader_module, None);
+                return Err(RenderError::from_vk(error, RenderError::Pipeline));
+            }
+        };
+        let push_constant_ranges = [PushConstantRange{stage_flags : ShaderStageFlags::COMPUTE, offset : 0, size : description.push_constant_size}];
+        let layout_create_info = PipelineLayoutCreateInfo{
+            s_type : StructureType::PIPELINE_LAYOUT_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : PipelineLayoutCreateFlags::empty(),
+            set_layout_count : 1,
+            p_set_layouts : &set_layout,
+            push_constant_range_count : if description.push_constant_size > 0{1}else{0},
+            p_push_constant_ranges : push_constant_ranges.as_ptr(),
+        };
+        let layout = match device.create_pipeline_layout(&layout_create_info, None){
+            Ok(layout) => {layout}
+            Err(error) => {
+                device.destroy_descriptor_set_layout(set_layout, None);
+                device.destroy_shader_module(shader_module, None);
+                return Err(RenderError::from_vk(error, RenderError::Pipeline));
+            }
+        };
+        let entry_point = std::ffi::CString::new("main").unwrap();
+        let pipeline_create_info = ComputePipelineCreateInfo{
+            s_type : StructureType::COMPUTE_PIPELINE_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : PipelineCreateFlags::empty(),
+            stage : PipelineShaderStageCreateInfo{
+                s_type : StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
+                p_next : std::ptr::null(),
+                flags : PipelineShaderStageCreateFlags::empty(),
+                stage : ShaderStageFlags::COMPUTE,
+                module : shader_module,
+                p_name : entry_point.as_ptr(),
+                p_specialization_info : std::ptr::null(),
+            },
+            layout,
+            base_pipeline_handle : Pipeline::null(),
+            base_pipeline_index : -1,
+        };
+        let pipeline = device.create_compute_pipelines(PipelineCache::null(), &[pipeline_create_info], None);
+        device.destroy_shader_module(shader_module, None);
+        let pipeline = match pipeline{
+            Ok(pipelines) => {pipelines[0]}
+            Err((_, error)) => {
+                device.destroy_pipeline_layout(layout, None);
+                device.destroy_descriptor_set_layout(set_layout, None);
+                return Err(RenderError::from_vk(error, RenderError::Pipeline));
+            }
+        };
+        let mut pipeline = Self{
+            pipeline,layout,set_layout,
+            descriptor_pool : DescriptorPool::null(),
+            descriptor_set : DescriptorSet::null(),
+            bindings : description.bindings.clone(),
+        };
+        if !bindings.is_empty(){
+            if let Err(error) = pipeline.allocate_descriptor_set(device){
+                pipeline.destroy(device);
+                return Err(error);
+            }
+        }
+        return Ok(pipeline);
+    }
+    unsafe fn allocate_descriptor_set(&mut self, device : &Device) -> Result<(), RenderError>{
+        let pool_sizes = self.bindings.iter().map(|&ty| DescriptorPoolSize{ty, descriptor_count : 1}).collect::<Vec<_>>();
+        let pool_create_info = DescriptorPoolCreateInfo{
+            s_type : StructureType::DESCRIPTOR_POOL_CREATE_INFO,
+            p_next : std::ptr::null(),
+            flags : DescriptorPoolCreateFlags::empty(),
+            max_sets : 1,
+            pool_size_count : pool_sizes.len() as u32,
+            p_pool_sizes : pool_sizes.as_ptr(),
+        };
+        self.descriptor_pool = device.create_descriptor_pool(&pool_create_info, None).map_err(|error| RenderError::from_vk(error, RenderError::Pipeline))?;
+        let allocate_info = DescriptorSetAllocateInfo{
+            s_type : StructureType::DESCRIPTOR_SET_ALLOCATE_INFO,
+            p_next : std::ptr::null(),
+            descriptor_pool : self.descriptor_pool,
+            descriptor_set_count : 1,
+            p_set_layouts : &self.set_layout,
+        };
+        self.descriptor_set = device.allocate_descriptor_sets(&allocate_info).map_err(|error| RenderError::from_vk(error, RenderError::Pipeline))?[0];
+        return Ok(());
+    }
+    ///Points a buffer binding of the pipeline at the given buffer.
+    pub unsafe fn bind_buffer(&self, device : &Device, binding : u32, buffer : Buffer, offset : u64, range : u64) -> Result<(), RenderError>{
+        let descriptor_type = *self.bindings.get(binding as usize).ok_or(RenderError::UnknownBinding(binding))?;
+        let buffer_info = DescriptorBufferInfo{buffer, offset, range};
+        let write = WriteDescriptorSet{
+            s_type : StructureType::WRITE_DESCRIPTOR_SET,
+            p_next : std::ptr::null(),
+            dst_set : self.descriptor_set,
+            dst_binding : binding,
+            dst_array_element : 0,
+            descriptor_count : 1,
+            descriptor_type,
+            p_buffer_info : &buffer_info,
+            p_image_info : std::ptr::null(),
+            p_texel_buffer_view : std::ptr::null(),
+        };
+        device.update_descriptor_sets(&[write], &[]);
+        return Ok(());
+    }
+    pub unsafe fn destroy(&self, device : &Device){
+        device.destroy_pipeline(self.pipeline, None);
+        device.destroy_pipeline_layout(self.layout, None);
+        if self.descriptor_pool != DescriptorPool::null(){device.destroy_descriptor_pool(self.descriptor_pool, None)}
+        device.destroy_descriptor_set_layout(self.set_layout, None);
+    }
+}
+unsafe fn create_shader_module(device : &Device, code : &[u32]) -> Result<ShaderModule, RenderError>{
+    let shader_module_create_info = ShaderModuleCreateInfo{
+        s_type : StructureType::SHADER_MODULE_CREATE_INFO,
+        p_next : std::ptr::null(),
+        flags : ShaderModuleCreateFlags::empty(),
+        code_size : code.len() * 4,
+        p_code : code.as_ptr(),
+    };
+    return device.create_shader_module(&shader_module_create_info, None).map_err(|error| RenderError::from_vk(error, RenderError::Pipeline));
+}
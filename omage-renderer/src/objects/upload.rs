@@ -0,0 +1,141 @@
+use ash::Device;
+use ash::vk::{AccessFlags, BufferImageCopy, BufferUsageFlags, CommandBufferBeginInfo, CommandBufferUsageFlags, DependencyFlags, Extent2D, Extent3D, Format, ImageAspectFlags, ImageLayout, ImageMemoryBarrier, ImageSubresourceLayers, ImageSubresourceRange, ImageUsageFlags, MemoryPropertyFlags, Offset3D, PipelineStageFlags, Queue, StructureType, SubmitInfo, QUEUE_FAMILY_IGNORED};
+use crate::allocator::Allocator;
+use crate::error::RenderError;
+use crate::functions;
+use crate::objects::buffer::AllocatedBuffer;
+use crate::objects::image::AllocatedImageView;
+
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct Vertex{
+    pub position : [f32; 3],
+    pub normal : [f32; 3],
+    pub uv : [f32; 2],
+}
+
+///Asset data handed to the render thread, kept on the CPU side so it can be uploaded again after a device loss.
+#[derive(Clone, PartialEq, Debug)]
+pub enum GpuAsset{
+    ///Tightly packed RGBA8 pixels in sRGB.
+    Texture{width : u32, height : u32, pixels : Vec<u8>},
+    Mesh{vertices : Vec<Vertex>, indices : Vec<u32>},
+}
+pub enum GpuResource{
+    Texture(AllocatedImageView),
+    Mesh{vertices : AllocatedBuffer, indices : AllocatedBuffer, index_count : u32},
+}
+impl GpuResource{
+    pub unsafe fn new(device : &Device, allocator : &mut Allocator, queue : Queue, queue_family : u32, asset : &GpuAsset) -> Result<Self, RenderError>{
+        return match asset{
+            GpuAsset::Texture{width, height, pixels} => {Ok(GpuResource::Texture(upload_texture(device, allocator, queue, queue_family, *width, *height, pixels)?))}
+            GpuAsset::Mesh{vertices, indices} => {
+                let vertex_bytes = std::slice::from_raw_parts(vertices.as_ptr() as *const u8, std::mem::size_of_val(vertices.as_slice()));
+                let index_bytes = std::slice::from_raw_parts(indices.as_ptr() as *const u8, std::mem::size_of_val(indices.as_slice()));
+                let vertices = host_buffer(allocator, vertex_bytes, BufferUsageFlags::VERTEX_BUFFER, queue_family)?;
+                let indices = match host_buffer(allocator, index_bytes, BufferUsageFlags::INDEX_BUFFER, queue_family){
+                    Ok(buffer) => {buffer}
+                    Err(error) => {
+                        vertices.destroy(allocator);
+                        return Err(error);
+                    }
+                };
+                Ok(GpuResource::Mesh{vertices, indices, index_count : index_bytes.len() as u32 / 4})
+            }
+        }
+    }
+    pub unsafe fn destroy(&self, allocator : &mut Allocator){
+        match self{
+            GpuResource::Texture(image) => {image.destroy(allocator)}
+            GpuResource::Mesh{vertices, indices, ..} => {
+                vertices.destroy(allocator);
+                indices.destroy(allocator);
+            }
+        }
+    }
+}
+///Creates a host visible buffer holding the data, empty data still gets a minimal buffer.
+unsafe fn host_buffer(allocator : &mut Allocator, data : &[u8], usage : BufferUsageFlags, queue_family : u32) -> Result<AllocatedBuffer, RenderError>{
+    let buffer = AllocatedBuffer::new(allocator, data.len().max(4) as u64, usage, MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT, &[queue_family])?;
+    if let Err(error) = allocator.write(&buffer.allocation, data){
+        buffer.destroy(allocator);
+        return Err(error);
+    }
+    return Ok(buffer);
+}
+///Copies the pixels into a device local image through a staging buffer and waits for the copy to finish.
+unsafe fn upload_texture(device : &Device, allocator : &mut Allocator, queue : Queue, queue_family : u32, width : u32, height : u32, pixels : &[u8]) -> Result<AllocatedImageView, RenderError>{
+    let staging = host_buffer(allocator, pixels, BufferUsageFlags::TRANSFER_SRC, queue_family)?;
+    let image = match AllocatedImageView::new_2d(allocator, Extent2D{width, height}, Format::R8G8B8A8_SRGB, MemoryPropertyFlags::DEVICE_LOCAL, ImageUsageFlags::SAMPLED | ImageUsageFlags::TRANSFER_DST, ImageAspectFlags::COLOR){
+        Ok(image) => {image}
+        Err(error) => {
+            staging.destroy(allocator);
+            return Err(error);
+        }
+    };
+    let result = copy_to_image(device, queue, queue_family, &staging, &image, width, height);
+    staging.destroy(allocator);
+    if let Err(error) = result{
+        image.destroy(allocator);
+        return Err(error);
+    }
+    return Ok(image);
+}
+unsafe fn copy_to_image(device : &Device, queue : Queue, queue_family : u32, staging : &AllocatedBuffer, image : &AllocatedImageView, width : u32, height : u32) -> Result<(), RenderError>{
+    let map_error = |error| RenderError::from_vk(error, RenderError::Command);
+    let command_pool = functions::command::create_command_pool(device, queue_family)?;
+    let fence = match functions::command::create_fence(device, false){
+        Ok(fence) => {fence}
+        Err(error) => {
+            device.destroy_command_pool(command_pool, None);
+            return Err(error);
+        }
+    };
+    let result = (|| {
+        let command_buffer = functions::command::allocate_command_buffer(device, command_pool)?;
+        let begin_info = CommandBufferBeginInfo{
+            s_type : StructureType::COMMAND_BUFFER_BEGIN_INFO,
+            p_next : std::ptr::null(),
+            flags : CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            p_inheritance_info : std::ptr::null(),
+        };
+        device.begin_command_buffer(command_buffer, &begin_info).map_err(map_error)?;
+        let subresource_range = ImageSubresourceRange{aspect_mask : ImageAspectFlags::COLOR, base_mip_level : 0, level_count : 1, base_array_layer : 0, layer_count : 1};
+        let barrier = |old_layout, new_layout, src_access_mask, dst_access_mask| ImageMemoryBarrier{
+            s_type : StructureType::IMAGE_MEMORY_BARRIER,
+            p_next : std::ptr::null(),
+            src_access_mask,dst_access_mask,old_layout,new_layout,subresource_range,
+            src_queue_family_index : QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index : QUEUE_FAMILY_IGNORED,
+            image : image.image.image,
+        };
+        device.cmd_pipeline_barrier(command_buffer, PipelineStageFlags::TOP_OF_PIPE, PipelineStageFlags::TRANSFER, DependencyFlags::empty(), &[], &[], &[barrier(ImageLayout::UNDEFINED, ImageLayout::TRANSFER_DST_OPTIMAL, AccessFlags::empty(), AccessFlags::TRANSFER_WRITE)]);
+        let region = BufferImageCopy{
+            buffer_offset : 0,
+            buffer_row_length : 0,
+            buffer_image_height : 0,
+            image_subresource : ImageSubresourceLayers{aspect_mask : ImageAspectFlags::COLOR, mip_level : 0, base_array_layer : 0, layer_count : 1},
+            image_offset : Offset3D{x : 0, y : 0, z : 0},
+            image_extent : Extent3D{width, height, depth : 1},
+        };
+        device.cmd_copy_buffer_to_image(command_buffer, staging.buffer, image.image.image, ImageLayout::TRANSFER_DST_OPTIMAL, &[region]);
+        device.cmd_pipeline_barrier(command_buffer, PipelineStageFlags::TRANSFER, PipelineStageFlags::FRAGMENT_SHADER, DependencyFlags::empty(), &[], &[], &[barrier(ImageLayout::TRANSFER_DST_OPTIMAL, ImageLayout::SHADER_READ_ONLY_OPTIMAL, AccessFlags::TRANSFER_WRITE, AccessFlags::SHADER_READ)]);
+        device.end_command_buffer(command_buffer).map_err(map_error)?;
+        let submit_info = SubmitInfo{
+            s_type : StructureType::SUBMIT_INFO,
+            p_next : std::ptr::null(),
+            wait_semaphore_count : 0,
+            p_wait_semaphores : std::ptr::null(),
+            p_wait_dst_stage_mask : std::ptr::null(),
+            command_buffer_count : 1,
+            p_command_buffers : &command_buffer,
+            signal_semaphore_count : 0,
+            p_signal_semaphores : std::ptr::null(),
+        };
+        device.queue_submit(queue, &[submit_info], fence).map_err(map_error)?;
+        device.wait_for_fences(&[fence], true, u64::MAX).map_err(map_error)
+    })();
+    device.destroy_fence(fence, None);
+    device.destroy_command_pool(command_pool, None);
+    return result;
+}
@@ -0,0 +1,30 @@
+///A column major 4x4 matrix.
+pub type Matrix = [[f32; 4]; 4];
+
+///Everything the renderer draws in a frame, extracted from the engine's world.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct RenderScene{
+    pub camera : Option<CameraView>,
+    pub meshes : Vec<MeshInstance>,
+    pub sprites : Vec<SpriteInstance>,
+}
+#[derive(Clone, PartialEq, Debug)]
+pub struct CameraView{
+    pub view : Matrix,
+    pub projection : Matrix,
+    pub clear_color : [f32; 4],
+}
+#[derive(Clone, PartialEq, Debug)]
+pub struct MeshInstance{
+    ///The id the mesh was uploaded under.
+    pub mesh : u64,
+    pub transform : Matrix,
+}
+#[derive(Clone, PartialEq, Debug)]
+pub struct SpriteInstance{
+    ///The id the texture was uploaded under.
+    pub texture : u64,
+    pub size : [f32; 2],
+    pub color : [f32; 4],
+    pub transform : Matrix,
+}
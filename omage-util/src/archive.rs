@@ -0,0 +1,316 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use flate2::Compression as Level;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use memmap2::Mmap;
+
+const MAGIC : &[u8; 8] = b"OMAGEPAK";
+///The archive format version, archives of other versions are rejected.
+pub const FORMAT_VERSION : u32 = 1;
+const HEADER_SIZE : usize = 32;
+///Deflate cannot expand data by more than about 1032 to 1, entries claiming more are damaged.
+const MAX_DEFLATE_RATIO : u64 = 1032;
+///The most memory reserved up front for a decompressed entry, the size in the table of contents is not trusted beyond that.
+const MAX_PREALLOCATION : u64 = 64 * 1024 * 1024;
+
+///The hash stored for every entry, taken over the uncompressed content.
+pub fn content_hash(data : &[u8]) -> u128{
+    return xxhash_rust::xxh3::xxh3_128(data);
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ArchiveError{
+    Io(String),
+    ///The file is not an archive or its table of contents is damaged.
+    Format(String),
+    Version(u32),
+    ///The content of the entry does not match its hash.
+    Hash(String),
+    Missing(String),
+}
+impl Display for ArchiveError{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return match self{
+            ArchiveError::Io(error) => {write!(f, "io error, {}", error)}
+            ArchiveError::Format(error) => {write!(f, "invalid archive, {}", error)}
+            ArchiveError::Version(version) => {write!(f, "unsupported archive version {}, expected {}", version, FORMAT_VERSION)}
+            ArchiveError::Hash(path) => {write!(f, "the content of {} does not match its hash", path)}
+            ArchiveError::Missing(path) => {write!(f, "the archive has no entry {}", path)}
+        }
+    }
+}
+impl std::error::Error for ArchiveError{}
+impl From<std::io::Error> for ArchiveError{
+    fn from(error : std::io::Error) -> Self {
+        return ArchiveError::Io(error.to_string());
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Compression{
+    Store,
+    ///Deflate, entries that do not get smaller are stored instead.
+    Deflate,
+}
+impl Compression{
+    fn id(&self) -> u8{
+        return match self{
+            Compression::Store => {0}
+            Compression::Deflate => {1}
+        }
+    }
+    fn from_id(id : u8) -> Option<Self>{
+        return match id{
+            0 => {Some(Compression::Store)}
+            1 => {Some(Compression::Deflate)}
+            _ => {None}
+        }
+    }
+}
+
+///An entry of the table of contents, the offset is aligned to the alignment of the archive.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ArchiveEntry{
+    pub offset : u64,
+    pub stored_size : u64,
+    pub size : u64,
+    pub hash : u128,
+    pub compression : Compression,
+}
+
+///A memory mapped archive, stored entries are read straight from the mapping.
+pub struct Archive{
+    map : Mmap,
+    alignment : u32,
+    entries : BTreeMap<String, ArchiveEntry>,
+}
+impl Archive{
+    pub fn open(path : &Path) -> Result<Self, ArchiveError>{
+        let file = File::open(path)?;
+        //The archive is only read, modifying it while it is mapped is not supported.
+        let map = unsafe{Mmap::map(&file)}?;
+        if map.len() < HEADER_SIZE || &map[0..8] != MAGIC{return Err(ArchiveError::Format("missing the archive header".to_owned()))}
+        let mut header = Cursor{data : &map[8..HEADER_SIZE]};
+        let version = header.u32()?;
+        if version != FORMAT_VERSION{return Err(ArchiveError::Version(version))}
+        let alignment = header.u32()?;
+        let toc_offset = header.u64()? as usize;
+        let count = header.u32()?;
+        let mut toc = Cursor{data : map.get(toc_offset..).ok_or_else(|| ArchiveError::Format("the table of contents lies outside of the file".to_owned()))?};
+        let mut entries = BTreeMap::new();
+        for _ in 0..count{
+            let entry = ArchiveEntry{
+                offset : toc.u64()?,
+                stored_size : toc.u64()?,
+                size : toc.u64()?,
+                hash : toc.u128()?,
+                compression : Compression::from_id(toc.u8()?).ok_or_else(|| ArchiveError::Format("unknown compression".to_owned()))?,
+            };
+            let length = toc.u16()? as usize;
+            let path = String::from_utf8(toc.take(length)?.to_vec()).map_err(|error| ArchiveError::Format(error.to_string()))?;
+            if entry.offset.checked_add(entry.stored_size).is_none_or(|end| end > toc_offset as u64){
+                return Err(ArchiveError::Format(format!("the entry {} lies outside of the data", path)));
+            }
+            let max_size = match entry.compression{
+                Compression::Store => {entry.stored_size}
+                Compression::Deflate => {entry.stored_size.saturating_mul(MAX_DEFLATE_RATIO)}
+            };
+            if entry.size > max_size{
+                return Err(ArchiveError::Format(format!("the entry {} claims a size of {} bytes from {} stored bytes", path, entry.size, entry.stored_size)));
+            }
+            entries.insert(path, entry);
+        }
+        return Ok(Self{map, alignment, entries});
+    }
+    pub fn alignment(&self) -> u32{
+        return self.alignment;
+    }
+    pub fn entries(&self) -> &BTreeMap<String, ArchiveEntry>{
+        return &self.entries;
+    }
+    pub fn contains(&self, path : &str) -> bool{
+        return self.entries.contains_key(path);
+    }
+    ///Reads an entry and checks its hash, stored entries borrow from the mapping.
+    pub fn read(&self, path : &str) -> Result<Cow<'_, [u8]>, ArchiveError>{
+        let entry = self.entries.get(path).ok_or_else(|| ArchiveError::Missing(path.to_owned()))?;
+        let stored = &self.map[entry.offset as usize..(entry.offset + entry.stored_size) as usize];
+        let data = match entry.compression{
+            Compression::Store => {Cow::Borrowed(stored)}
+            Compression::Deflate => {
+                let mut data = Vec::with_capacity(entry.size.min(MAX_PREALLOCATION) as usize);
+                //One byte more than the expected size is enough to notice a mismatch without inflating all of it.
+                DeflateDecoder::new(stored).take(entry.size.saturating_add(1)).read_to_end(&mut data)?;
+                Cow::Owned(data)
+            }
+        };
+        if data.len() as u64 != entry.size || content_hash(&data) != entry.hash{return Err(ArchiveError::Hash(path.to_owned()))}
+        return Ok(data);
+    }
+}
+
+///Collects files and writes them as an archive.
+pub struct ArchiveBuilder{
+    alignment : u32,
+    entries : BTreeMap<String, (Vec<u8>, Compression)>,
+}
+impl ArchiveBuilder{
+    ///The alignment of the entries has to be a power of two.
+    pub fn new(alignment : u32) -> Self{
+        return Self{alignment, entries : BTreeMap::new()};
+    }
+    pub fn add(&mut self, path : &str, data : Vec<u8>, compression : Compression){
+        self.entries.insert(path.to_owned(), (data, compression));
+    }
+    ///Adds every file below the directory under its relative path, separated by forward slashes.
+    pub fn add_directory(&mut self, directory : &Path, compression : Compression) -> Result<(), ArchiveError>{
+        let mut pending = vec![directory.to_path_buf()];
+        while let Some(current) = pending.pop(){
+            for entry in std::fs::read_dir(current)?{
+                let path = entry?.path();
+                if path.is_dir(){pending.push(path); continue;}
+                let relative = path.strip_prefix(directory).map_err(|error| ArchiveError::Io(error.to_string()))?;
+                self.add(&relative_path(relative), std::fs::read(&path)?, compression);
+            }
+        }
+        return Ok(());
+    }
+    pub fn len(&self) -> usize{
+        return self.entries.len();
+    }
+    pub fn is_empty(&self) -> bool{
+        return self.entries.is_empty();
+    }
+    pub fn write(&self, output : &Path) -> Result<(), ArchiveError>{
+        if !self.alignment.is_power_of_two(){return Err(ArchiveError::Format(format!("the alignment {} is not a power of two", self.alignment)))}
+        let mut data = vec![0; HEADER_SIZE];
+        let mut toc = vec![];
+        for (path, (content, compression)) in &self.entries{
+            let (stored, compression) = match compression{
+                Compression::Store => {(Cow::Borrowed(content.as_slice()), Compression::Store)}
+                Compression::Deflate => {
+                    let mut encoder = DeflateEncoder::new(vec![], Level::best());
+                    encoder.write_all(content)?;
+                    let compressed = encoder.finish()?;
+                    if compressed.len() < content.len() {(Cow::Owned(compressed), Compression::Deflate)} else {(Cow::Borrowed(content.as_slice()), Compression::Store)}
+                }
+            };
+            data.resize(align(data.len(), self.alignment as usize), 0);
+            let path_length = u16::try_from(path.len()).map_err(|_| ArchiveError::Format(format!("the path {} is too long", path)))?;
+            toc.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            toc.extend_from_slice(&(stored.len() as u64).to_le_bytes());
+            toc.extend_from_slice(&(content.len() as u64).to_le_bytes());
+            toc.extend_from_slice(&content_hash(content).to_le_bytes());
+            toc.push(compression.id());
+            toc.extend_from_slice(&path_length.to_le_bytes());
+            toc.extend_from_slice(path.as_bytes());
+            data.extend_from_slice(&stored);
+        }
+        let toc_offset = data.len() as u64;
+        data.extend_from_slice(&toc);
+        data[0..8].copy_from_slice(MAGIC);
+        data[8..12].copy_from_slice(&FORMAT_VERSION.to_le_bytes());
+        data[12..16].copy_from_slice(&self.alignment.to_le_bytes());
+        data[16..24].copy_from_slice(&toc_offset.to_le_bytes());
+        data[24..28].copy_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        if let Some(parent) = output.parent(){std::fs::create_dir_all(parent)?}
+        std::fs::write(output, data)?;
+        return Ok(());
+    }
+}
+
+///Joins the components of a relative path with forward slashes, the separator used for assets on every platform.
+pub fn relative_path(path : &Path) -> String{
+    return path.components().map(|component| component.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+}
+fn align(offset : usize, alignment : usize) -> usize{
+    return (offset + alignment - 1) & !(alignment - 1);
+}
+
+///Reads little endian values from the front of a byte slice.
+struct Cursor<'a>{
+    data : &'a [u8],
+}
+impl<'a> Cursor<'a>{
+    fn take(&mut self, length : usize) -> Result<&'a [u8], ArchiveError>{
+        if self.data.len() < length{return Err(ArchiveError::Format("unexpected end of the table of contents".to_owned()))}
+        let (taken, rest) = self.data.split_at(length);
+        self.data = rest;
+        return Ok(taken);
+    }
+    fn u8(&mut self) -> Result<u8, ArchiveError>{
+        return Ok(self.take(1)?[0]);
+    }
+    fn u16(&mut self) -> Result<u16, ArchiveError>{
+        return Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()));
+    }
+    fn u32(&mut self) -> Result<u32, ArchiveError>{
+        return Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()));
+    }
+    fn u64(&mut self) -> Result<u64, ArchiveError>{
+        return Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()));
+    }
+    fn u128(&mut self) -> Result<u128, ArchiveError>{
+        return Ok(u128::from_le_bytes(self.take(16)?.try_into().unwrap()));
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use std::path::PathBuf;
+    use super::*;
+
+    ///Writes the archive to a file of its own in the temporary directory.
+    fn write(name : &str, builder : &ArchiveBuilder) -> PathBuf{
+        let path = std::env::temp_dir().join(format!("omage-archive-{}-{}.pak", std::process::id(), name));
+        builder.write(&path).unwrap();
+        return path;
+    }
+
+    #[test]
+    fn entries_round_trip(){
+        let compressible = "omage ".repeat(1000).into_bytes();
+        let mut builder = ArchiveBuilder::new(16);
+        builder.add("stored.txt", b"stored".to_vec(), Compression::Store);
+        builder.add("scenes/deflated.txt", compressible.clone(), Compression::Deflate);
+        builder.add("incompressible.bin", vec![7], Compression::Deflate);
+        builder.add("empty", vec![], Compression::Store);
+        let path = write("round-trip", &builder);
+        let archive = Archive::open(&path).unwrap();
+        assert_eq!(archive.alignment(), 16);
+        assert_eq!(archive.entries().len(), 4);
+        assert!(archive.entries().values().all(|entry| entry.offset % 16 == 0));
+        assert_eq!(archive.read("stored.txt").unwrap().as_ref(), b"stored");
+        assert_eq!(archive.read("scenes/deflated.txt").unwrap().as_ref(), compressible.as_slice());
+        assert_eq!(archive.entries()["scenes/deflated.txt"].compression, Compression::Deflate);
+        assert_eq!(archive.read("incompressible.bin").unwrap().as_ref(), &[7]);
+        assert_eq!(archive.entries()["incompressible.bin"].compression, Compression::Store);
+        assert!(archive.read("empty").unwrap().is_empty());
+        assert_eq!(archive.read("missing"), Err(ArchiveError::Missing("missing".to_owned())));
+        std::fs::remove_file(path).unwrap();
+    }
+    #[test]
+    fn damaged_archives_are_rejected(){
+        let mut builder = ArchiveBuilder::new(8);
+        builder.add("a.txt", b"content".to_vec(), Compression::Store);
+        let path = write("damaged", &builder);
+        let mut data = std::fs::read(&path).unwrap();
+        data[HEADER_SIZE] ^= 1;
+        std::fs::write(&path, &data).unwrap();
+        assert_eq!(Archive::open(&path).unwrap().read("a.txt"), Err(ArchiveError::Hash("a.txt".to_owned())));
+        data[8..12].copy_from_slice(&2u32.to_le_bytes());
+        std::fs::write(&path, &data).unwrap();
+        assert_eq!(Archive::open(&path).err(), Some(ArchiveError::Version(2)));
+        std::fs::remove_file(path).unwrap();
+    }
+    #[test]
+    fn alignments_have_to_be_powers_of_two(){
+        let path = std::env::temp_dir().join(format!("omage-archive-{}-alignment.pak", std::process::id()));
+        assert!(matches!(ArchiveBuilder::new(12).write(&path), Err(ArchiveError::Format(_))));
+    }
+}
@@ -0,0 +1,340 @@
+use std::fmt::{Display, Formatter};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_derive::Deserialize;
+use serde_json::value::RawValue;
+///The format independent value older files are migrated on, with the data model of JSON.
+pub use serde_json::Value;
+use crate::archive::content_hash;
+use crate::ron_value;
+
+///The reserved key holding the schema version of TOML files without a header, so files can keep a field named `version`.
+pub const VERSION_KEY : &str = "__version";
+const BINARY_MAGIC : &[u8; 8] = b"OMAGEBIN";
+const BINARY_HEADER_SIZE : usize = 28;
+///Starts the header line of text formats, after the comment marker of the format.
+const TEXT_HEADER : &str = "omage file";
+
+///A file with a schema version, files of older versions are migrated step by step before they are deserialized.
+///Files written before versioning count as version 1.
+pub trait Versioned{
+    ///The current schema version, raised whenever a field is renamed, moved or changes its meaning.
+    const VERSION : u32 = 1;
+    ///Upgrades the parsed file from `version` to `version + 1`, added fields are better left to `#[serde(default)]`.
+    ///Enum variants without data are strings and variants with data objects with the variant as the only key, in every format.
+    fn migrate(version : u32, _value : &mut Value) -> Result<(), String>{
+        return Err(format!("no migration from version {}", version));
+    }
+}
+
+///How a file is serialized, every format starts with a header holding the schema version and a hash of the content.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Format{
+    Toml,
+    Ron,
+    ///The content is wrapped in an object holding the version and hash, as JSON has no comments.
+    Json,
+    ///Compact and not meant to be edited, files of another version or with a wrong hash are rejected.
+    Binary,
+}
+impl Format{
+    pub fn extension(&self) -> &'static str{
+        return match self{
+            Format::Toml => {"toml"}
+            Format::Ron => {"ron"}
+            Format::Json => {"json"}
+            Format::Binary => {"bin"}
+        }
+    }
+    fn comment(&self) -> Option<&'static str>{
+        return match self{
+            Format::Toml => {Some("#")}
+            Format::Ron => {Some("//")}
+            Format::Json | Format::Binary => {None}
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ParseError{
+    ///The file has a version that cannot be read, like a stale cache, with the found and the expected version.
+    Version(u32, u32),
+    ///The content of a binary file does not match its hash.
+    Hash,
+    Invalid(String),
+}
+impl Display for ParseError{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return match self{
+            ParseError::Version(found, expected) => {write!(f, "the file has the version {}, expected {}, binary files are not migrated", found, expected)}
+            ParseError::Hash => {write!(f, "the content does not match its hash")}
+            ParseError::Invalid(error) => {write!(f, "{}", error)}
+        }
+    }
+}
+impl std::error::Error for ParseError{}
+
+///A parsed file, with the version it was stored with.
+pub struct Parsed<T>{
+    pub file : T,
+    pub version : u32,
+    ///The content does not match the hash in the header, a text file was edited by hand.
+    pub edited : bool,
+}
+
+///The version and hash found in the header of a file.
+struct Header{
+    version : u32,
+    hash : u128,
+}
+
+#[derive(Deserialize)]
+struct JsonEnvelope<'a>{
+    version : u32,
+    hash : String,
+    #[serde(borrow)]
+    content : &'a RawValue,
+}
+
+///Parses a file, checks its header and migrates text files of older versions.
+///Text files of newer versions are parsed as they are, unknown fields are ignored, binary files of another version are rejected.
+pub fn parse<T : Versioned + DeserializeOwned>(data : &[u8], format : Format) -> Result<Parsed<T>, ParseError>{
+    let (header, content) = split_header(data, format).map_err(ParseError::Invalid)?;
+    let edited = header.as_ref().is_some_and(|header| header.hash != content_hash(content));
+    if edited && format == Format::Binary{return Err(ParseError::Hash)}
+    let header_version = header.map(|header| header.version);
+    //Binary files cannot be read as another version without misparsing them.
+    if format == Format::Binary && header_version != Some(T::VERSION){return Err(ParseError::Version(header_version.unwrap_or(1), T::VERSION))}
+    let invalid = |error : String| ParseError::Invalid(error);
+    //Files of the current version are read straight from their format, older ones are migrated as a value holding every format's data first.
+    let migrate = |mut value : Value, version : u32| -> Result<T, ParseError>{
+        for from in version..T::VERSION{
+            T::migrate(from, &mut value).map_err(|error| invalid(format!("failed to migrate from version {}, {}", from, error)))?;
+        }
+        return serde_json::from_value(value).map_err(|error| invalid(error.to_string()));
+    };
+    let (file, version) = match format{
+        Format::Toml => {
+            let mut value : toml::Value = toml::from_slice(content).map_err(|error| invalid(error.to_string()))?;
+            let table = value.as_table_mut().ok_or_else(|| invalid("the file is not a table".to_owned()))?;
+            let key_version = match table.remove(VERSION_KEY){
+                None => {None}
+                Some(toml::Value::Integer(version)) if version >= 1 => {Some(u32::try_from(version).map_err(|_| invalid(format!("invalid version {}", version)))?)}
+                Some(version) => {return Err(invalid(format!("invalid version {}", version)))}
+            };
+            let version = header_version.or(key_version).unwrap_or(1);
+            let file = if version < T::VERSION {migrate(serde_json::to_value(value).map_err(|error| invalid(error.to_string()))?, version)?} else {value.try_into().map_err(|error : toml::de::Error| invalid(error.to_string()))?};
+            (file, version)
+        }
+        Format::Ron => {
+            let version = header_version.unwrap_or(1);
+            let text = std::str::from_utf8(content).map_err(|error| invalid(error.to_string()))?;
+            let file = if version < T::VERSION {migrate(ron_value::parse(text).map_err(invalid)?, version)?} else {ron::from_str(text).map_err(|error| invalid(error.to_string()))?};
+            (file, version)
+        }
+        Format::Json => {
+            let version = header_version.unwrap_or(1);
+            let file = if version < T::VERSION {migrate(serde_json::from_slice(content).map_err(|error| invalid(error.to_string()))?, version)?} else {serde_json::from_slice(content).map_err(|error| invalid(error.to_string()))?};
+            (file, version)
+        }
+        Format::Binary => {(bincode::deserialize(content).map_err(|error| invalid(error.to_string()))?, T::VERSION)}
+    };
+    return Ok(Parsed{file, version, edited});
+}
+
+///Serializes the file with a header holding its current version and the hash of the content.
+pub fn serialize<T : Versioned + Serialize>(file : &T, format : Format) -> Result<Vec<u8>, String>{
+    let content = match format{
+        Format::Toml => {toml::to_vec(file).map_err(|error| error.to_string())?}
+        Format::Ron => {ron::ser::to_string_pretty(file, ron::ser::PrettyConfig::default()).map_err(|error| error.to_string())?.into_bytes()}
+        Format::Json => {serde_json::to_vec_pretty(file).map_err(|error| error.to_string())?}
+        Format::Binary => {bincode::serialize(file).map_err(|error| error.to_string())?}
+    };
+    let hash = content_hash(&content);
+    let mut data = match (format, format.comment()){
+        (Format::Binary, _) => {
+            let mut data = BINARY_MAGIC.to_vec();
+            data.extend_from_slice(&T::VERSION.to_le_bytes());
+            data.extend_from_slice(&hash.to_le_bytes());
+            data
+        }
+        (_, Some(comment)) => {format!("{} {} version = {}, hash = {:032x}\n", comment, TEXT_HEADER, T::VERSION, hash).into_bytes()}
+        (_, None) => {format!("{{\n\"version\": {},\n\"hash\": \"{:032x}\",\n\"content\": ", T::VERSION, hash).into_bytes()}
+    };
+    data.extend_from_slice(&content);
+    if format == Format::Json{data.extend_from_slice(b"\n}\n")}
+    return Ok(data);
+}
+
+///Separates the header from the content, text files without a header were written by hand or before headers existed.
+fn split_header(data : &[u8], format : Format) -> Result<(Option<Header>, &[u8]), String>{
+    let hash = |hash : &str| u128::from_str_radix(hash, 16).map_err(|_| format!("invalid hash {}", hash));
+    return match (format, format.comment()){
+        (Format::Binary, _) => {
+            if data.len() < BINARY_HEADER_SIZE || &data[0..8] != BINARY_MAGIC{return Err("missing the file header".to_owned())}
+            let version = u32::from_le_bytes(data[8..12].try_into().unwrap());
+            let hash = u128::from_le_bytes(data[12..28].try_into().unwrap());
+            Ok((Some(Header{version, hash}), &data[BINARY_HEADER_SIZE..]))
+        }
+        (_, Some(comment)) => {
+            let prefix = format!("{} {} ", comment, TEXT_HEADER);
+            if !data.starts_with(prefix.as_bytes()){return Ok((None, data))}
+            let end = data.iter().position(|byte| *byte == b'\n').unwrap_or(data.len());
+            let line = std::str::from_utf8(&data[prefix.len()..end]).map_err(|error| error.to_string())?;
+            let (version, hash_text) = line.trim_end().strip_prefix("version = ").and_then(|line| line.split_once(", hash = ")).ok_or_else(|| format!("invalid header {}", line))?;
+            let version = version.parse().map_err(|_| format!("invalid version {}", version))?;
+            Ok((Some(Header{version, hash : hash(hash_text)?}), &data[(end + 1).min(data.len())..]))
+        }
+        (_, None) => {
+            match serde_json::from_slice::<JsonEnvelope>(data){
+                Ok(envelope) => {
+                    let content = envelope.content.get().as_bytes();
+                    //The content borrows from the data, so its position gives the slice to hash.
+                    let start = content.as_ptr() as usize - data.as_ptr() as usize;
+                    Ok((Some(Header{version : envelope.version, hash : hash(&envelope.hash)?}), &data[start..start + content.len()]))
+                }
+                Err(_) => {Ok((None, data))}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use serde_derive::Serialize;
+    use super::*;
+
+    const FORMATS : [Format; 4] = [Format::Toml, Format::Ron, Format::Json, Format::Binary];
+
+    ///Version 2 renamed `name` to `title`, `version` is a field of the file itself.
+    #[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+    #[serde(default)]
+    struct Settings{
+        title : String,
+        version : u32,
+        values : Vec<i64>,
+    }
+    impl Versioned for Settings{
+        const VERSION : u32 = 2;
+        fn migrate(version : u32, value : &mut Value) -> Result<(), String>{
+            let table = value.as_object_mut().ok_or("the file is not a table")?;
+            return match version{
+                1 => {
+                    if let Some(name) = table.remove("name"){table.insert("title".to_owned(), name);}
+                    Ok(())
+                }
+                _ => {Err(format!("no migration from version {}", version))}
+            }
+        }
+    }
+    fn settings() -> Settings{
+        return Settings{title : "omage".to_owned(), version : 7, values : vec![1, 2, 3]};
+    }
+    fn migrated() -> Settings{
+        return Settings{title : "omage".to_owned(), version : 7, values : vec![]};
+    }
+
+    #[test]
+    fn every_format_round_trips(){
+        for format in FORMATS{
+            let data = serialize(&settings(), format).unwrap();
+            let parsed = parse::<Settings>(&data, format).unwrap();
+            assert_eq!(parsed.file, settings(), "{:?}", format);
+            assert_eq!(parsed.version, Settings::VERSION, "{:?}", format);
+            assert!(!parsed.edited, "{:?}", format);
+        }
+    }
+    #[test]
+    fn edited_text_files_are_flagged(){
+        for format in [Format::Toml, Format::Ron, Format::Json]{
+            let data = String::from_utf8(serialize(&settings(), format).unwrap()).unwrap().replace("\"omage\"", "\"edited\"");
+            let parsed = parse::<Settings>(data.as_bytes(), format).unwrap();
+            assert_eq!(parsed.file.title, "edited", "{:?}", format);
+            assert!(parsed.edited, "{:?}", format);
+        }
+    }
+    #[test]
+    fn binary_files_with_a_wrong_hash_are_rejected(){
+        let mut data = serialize(&settings(), Format::Binary).unwrap();
+        *data.last_mut().unwrap() ^= 1;
+        assert_eq!(parse::<Settings>(&data, Format::Binary).err(), Some(ParseError::Hash));
+    }
+    #[test]
+    fn binary_files_of_another_version_are_rejected(){
+        let mut data = serialize(&settings(), Format::Binary).unwrap();
+        data[8..12].copy_from_slice(&1u32.to_le_bytes());
+        assert_eq!(parse::<Settings>(&data, Format::Binary).err(), Some(ParseError::Version(1, 2)));
+    }
+    #[test]
+    fn old_text_files_are_migrated(){
+        let files = [
+            (Format::Toml, "name = \"omage\"\nversion = 7\n"),
+            (Format::Toml, "# omage file version = 1, hash = 0\nname = \"omage\"\nversion = 7\n"),
+            (Format::Toml, "__version = 1\nname = \"omage\"\nversion = 7\n"),
+            (Format::Ron, "(name: \"omage\", version: 7)"),
+            (Format::Ron, "// omage file version = 1, hash = 0\n(name: \"omage\", version: 7)"),
+            (Format::Json, "{\"name\": \"omage\", \"version\": 7, \"values\": []}"),
+            (Format::Json, "{\"version\": 1, \"hash\": \"0\", \"content\": {\"name\": \"omage\", \"version\": 7}}"),
+        ];
+        for (format, data) in files{
+            let parsed = parse::<Settings>(data.as_bytes(), format).unwrap();
+            assert_eq!(parsed.file, migrated(), "{:?} {}", format, data);
+            assert_eq!(parsed.version, 1, "{:?} {}", format, data);
+        }
+    }
+    #[test]
+    fn enums_and_nulls_survive_migrations(){
+        #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+        enum Shape{
+            Point,
+            Circle(f64),
+            Rectangle{width : f64, height : f64},
+        }
+        #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+        struct Shapes{
+            title : String,
+            shapes : Vec<Option<Shape>>,
+        }
+        impl Versioned for Shapes{
+            const VERSION : u32 = 2;
+            fn migrate(version : u32, value : &mut Value) -> Result<(), String>{
+                return Settings::migrate(version, value);
+            }
+        }
+        let shapes = Shapes{title : "omage".to_owned(), shapes : vec![Some(Shape::Point), None, Some(Shape::Circle(1.0)), Some(Shape::Rectangle{width : 2.0, height : 3.0})]};
+        //TOML has no null, so its lists cannot hold the missing shape.
+        let expected = |format : Format| {
+            let mut shapes = shapes.clone();
+            if format == Format::Toml{shapes.shapes.remove(1);}
+            return shapes;
+        };
+        let files = [
+            (Format::Toml, "name = \"omage\"\nshapes = [\"Point\", {Circle = 1.0}, {Rectangle = {width = 2.0, height = 3.0}}]\n"),
+            (Format::Ron, "(name: \"omage\", shapes: [Some(Point), None, Some(Circle(1.0)), Some(Rectangle(width: 2.0, height: 3.0))])"),
+            (Format::Json, "{\"name\": \"omage\", \"shapes\": [\"Point\", null, {\"Circle\": 1.0}, {\"Rectangle\": {\"width\": 2.0, \"height\": 3.0}}]}"),
+        ];
+        for (format, data) in files{
+            assert_eq!(parse::<Shapes>(data.as_bytes(), format).unwrap().file, expected(format), "{:?}", format);
+        }
+        //The TOML serializer cannot write enum variants holding data.
+        for format in [Format::Ron, Format::Json, Format::Binary]{
+            let data = serialize(&expected(format), format).unwrap();
+            assert_eq!(parse::<Shapes>(&data, format).unwrap().file, expected(format), "{:?}", format);
+        }
+    }
+    #[test]
+    fn current_toml_files_keep_their_version_field(){
+        let parsed = parse::<Settings>(b"__version = 2\ntitle = \"omage\"\nversion = 7\n", Format::Toml).unwrap();
+        assert_eq!(parsed.file, migrated());
+        assert_eq!(parsed.version, 2);
+    }
+    #[test]
+    fn missing_migrations_are_reported(){
+        #[derive(Deserialize, Debug)]
+        struct Unmigrated{}
+        impl Versioned for Unmigrated{
+            const VERSION : u32 = 2;
+        }
+        assert!(matches!(parse::<Unmigrated>(b"", Format::Toml), Err(ParseError::Invalid(_))));
+    }
+}
@@ -1,79 +1,370 @@
-use std::fs::OpenOptions;
-use std::path::PathBuf;
+#![allow(clippy::needless_return)]
+pub mod archive;
+pub mod config;
+pub mod logging;
+pub mod vfs;
+mod ron_value;
+
+use std::any::Any;
+use std::ffi::OsString;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::panic::UnwindSafe;
+use std::sync::Arc;
+use std::time::Duration;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use slog::{Drain, Duplicate, Logger, o, warn};
-use slog_async::Async;
+use slog::{Drain, Duplicate, Filter, FnValue, Logger, Never, o, Record, SendSyncRefUnwindSafeDrain, debug, info, warn};
 use slog_term::{FullFormat, PlainSyncDecorator, TermDecorator};
+use crate::config::{Format, ParseError, Versioned};
+use crate::logging::{prune_logs, FlushableAsync, LogDirectory, LoggingConfig, RotatingFile};
+use crate::vfs::{priority, DirectorySource, Vfs, VfsError};
+
+///The rolling backups kept of overwritten files under `config://` and `data://`.
+const CONFIG_BACKUPS : usize = 1;
+const DATA_BACKUPS : usize = 3;
+
+///Overrides of the directories used by the path manager, unset ones come from the platform.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct PathOptions{
+    ///Keeps config, cache, data and logs in directories beside the executable.
+    pub portable : bool,
+    pub config_directory : Option<PathBuf>,
+    pub cache_directory : Option<PathBuf>,
+    pub data_directory : Option<PathBuf>,
+    pub assets_directory : Option<PathBuf>,
+}
+impl PathOptions{
+    ///Reads `OMAGE_PORTABLE`, `OMAGE_CONFIG_DIR`, `OMAGE_CACHE_DIR`, `OMAGE_DATA_DIR` and `OMAGE_ASSETS_DIR`.
+    ///A file named `portable` next to the executable also turns on portable mode.
+    pub fn from_environment() -> Self{
+        let variable = |name : &str| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+        return Self{
+            portable : variable("OMAGE_PORTABLE").is_some_and(|value| value != Path::new("0")) || PathManager::executable_directory().join("portable").exists(),
+            config_directory : variable("OMAGE_CONFIG_DIR"),
+            cache_directory : variable("OMAGE_CACHE_DIR"),
+            data_directory : variable("OMAGE_DATA_DIR"),
+            assets_directory : variable("OMAGE_ASSETS_DIR"),
+        };
+    }
+    ///Reads the environment like `from_environment`, then the flags `--portable`, `--config-dir`, `--cache-dir`, `--data-dir` and `--assets-dir`, which take precedence.
+    ///Only called by binaries that want these flags, the arguments exclude the program name and unknown ones are skipped.
+    pub fn from_args<I : IntoIterator<Item = OsString>>(arguments : I) -> Self{
+        let mut options = Self::from_environment();
+        let mut arguments = arguments.into_iter();
+        while let Some(argument) = arguments.next(){
+            let argument = argument.to_string_lossy().into_owned();
+            if argument == "--portable"{options.portable = true; continue;}
+            let (flag, value) = match argument.split_once('='){
+                Some((flag, value)) => {(flag.to_owned(), Some(PathBuf::from(value)))}
+                None => {(argument, None)}
+            };
+            let directory = match flag.as_str(){
+                "--config-dir" => {&mut options.config_directory}
+                "--cache-dir" => {&mut options.cache_directory}
+                "--data-dir" => {&mut options.data_directory}
+                "--assets-dir" => {&mut options.assets_directory}
+                _ => {continue}
+            };
+            *directory = value.or_else(|| arguments.next().map(PathBuf::from));
+        }
+        return options;
+    }
+}
 
 #[derive(Clone)]
 pub struct PathManager{
+    app_name : String,
     config_directory : PathBuf,
     cache_directory : PathBuf,
+    data_directory : PathBuf,
+    assets_directory : PathBuf,
+    portable : bool,
+    vfs : Vfs,
 }
 impl PathManager{
+    ///Uses the directories given by the environment, see `PathOptions::from_environment`.
     pub fn new(app_name : &str) -> Self{
-        let project_dirs = directories::ProjectDirs::from("com", "omage", app_name).unwrap();
-        return Self{
-            config_directory : project_dirs.config_dir().to_path_buf(),
-            cache_directory : project_dirs.cache_dir().to_path_buf(),
-        }
+        return Self::with_options(app_name, PathOptions::from_environment());
+    }
+    ///Mounts the assets, the user's mod directory and the written assets in the data directory under `assets://`, and the config, cache and data directories under `config://`, `cache://` and `data://`.
+    ///Without platform directories, like on a machine without a home directory, the portable layout is used.
+    pub fn with_options(app_name : &str, options : PathOptions) -> Self{
+        let executable_directory = Self::executable_directory();
+        let project_dirs = if options.portable {None} else {directories::ProjectDirs::from("com", "omage", app_name)};
+        let portable = project_dirs.is_none();
+        let (config_directory, cache_directory, data_directory) = match &project_dirs{
+            Some(project_dirs) => {(project_dirs.config_dir().to_path_buf(), project_dirs.cache_dir().to_path_buf(), project_dirs.data_dir().to_path_buf())}
+            None => {(executable_directory.join("config"), executable_directory.join("cache"), executable_directory.join("data"))}
+        };
+        let path_manager = Self{
+            app_name : app_name.to_string(),
+            config_directory : options.config_directory.unwrap_or(config_directory),
+            cache_directory : options.cache_directory.unwrap_or(cache_directory),
+            data_directory : options.data_directory.unwrap_or(data_directory),
+            assets_directory : options.assets_directory.unwrap_or_else(|| executable_directory.join("assets")),
+            portable,
+            vfs : Vfs::new(),
+        };
+        path_manager.vfs.mount("assets", Arc::new(DirectorySource::new(path_manager.assets_directory.clone())), priority::BASE);
+        path_manager.vfs.mount("assets", Arc::new(DirectorySource::read_only(path_manager.data_directory.join("mods"))), priority::MODS);
+        path_manager.vfs.mount("assets", Arc::new(DirectorySource::new(path_manager.data_directory.join("assets")).with_backups(DATA_BACKUPS)), priority::USER);
+        //Configs and saves keep the versions they replace, caches are rebuilt when lost.
+        path_manager.vfs.mount("config", Arc::new(DirectorySource::new(path_manager.config_directory.clone()).with_backups(CONFIG_BACKUPS)), priority::BASE);
+        path_manager.vfs.mount("cache", Arc::new(DirectorySource::new(path_manager.cache_directory.clone())), priority::BASE);
+        path_manager.vfs.mount("data", Arc::new(DirectorySource::new(path_manager.data_directory.clone()).with_backups(DATA_BACKUPS)), priority::BASE);
+        return path_manager;
+    }
+    ///Whether config, cache and data live beside the executable, by request or because the platform has no directories for them.
+    pub fn is_portable(&self) -> bool{
+        return self.portable;
+    }
+    pub fn config_directory(&self) -> &Path{
+        return &self.config_directory;
+    }
+    pub fn cache_directory(&self) -> &Path{
+        return &self.cache_directory;
     }
+    ///Save games and other user data, kept apart from the config so resetting one leaves the other.
+    pub fn data_directory(&self) -> &Path{
+        return &self.data_directory;
+    }
+    ///The mounted file sources, shared by every clone of the path manager.
+    pub fn vfs(&self) -> &Vfs{
+        return &self.vfs;
+    }
+    pub fn app_name(&self) -> &str{
+        return &self.app_name;
+    }
+    ///The assets shipped with the application, next to the executable unless overridden.
+    pub fn assets_directory(&self) -> &Path{
+        return &self.assets_directory;
+    }
+    ///The packed assets of a shipped build, used in place of the assets directory when present.
+    pub fn assets_archive(&self) -> PathBuf{
+        return Self::executable_directory().join("assets.pak");
+    }
+    fn executable_directory() -> PathBuf{
+        return std::env::current_exe().ok().and_then(|path| path.parent().map(Path::to_path_buf)).unwrap_or_default();
+    }
+    ///Logs to the terminal and to a file in the logs directory, as configured by logging.toml.
     pub fn create_logger(&self) -> Logger{
+        let (config, config_error) = self.logging_config();
         let term_decorator = TermDecorator::new().build();
         let term_drain = FullFormat::new(term_decorator).build().fuse();
-        let async_term_drain = Async::new(term_drain).build().fuse();
-        let log_file_name = chrono::Utc::now().format("%Y-%m-%d-%H-%M-%S");
-        let log_file_path = self.config_directory.join(format!("./logs/{}.log", log_file_name));
-        if !log_file_path.parent().unwrap().exists(){std::fs::create_dir_all(log_file_path.parent().unwrap()).expect("Failed to create log directory");}
-        let log_file = OpenOptions::new().create(true).write(true).truncate(true).open(log_file_path).unwrap();
-        let file_decorator = PlainSyncDecorator::new(log_file);
-        let file_drain = FullFormat::new(file_decorator).build().fuse();
-        let async_file_drain = Async::new(file_drain).build().fuse();
-        let full_drain = Duplicate::new(async_term_drain, async_file_drain).fuse();
-        return Logger::root(full_drain, o!());
-    }
-    fn get_path(&self, name : &str, file_type : FileType) -> PathBuf{
+        let term_config = config.clone();
+        let term_drain = Filter::new(term_drain, move |record : &Record| term_config.accepts(record, term_config.terminal_level)).fuse();
+        let mut drain : Box<dyn SendSyncRefUnwindSafeDrain<Ok = (), Err = Never> + UnwindSafe> = Box::new(Self::async_drain(term_drain));
+        let log_directory = self.logs_directory_of(&config);
+        let log_name = chrono::Utc::now().format("%Y-%m-%d-%H-%M-%S").to_string();
+        //A failed prune only leaves old files behind, the new log file is created either way.
+        let mut errors = prune_logs(&log_directory, config.max_files.map(|max_files| max_files.saturating_sub(if config.json {2} else {1})), config.max_age_days.map(|days| Duration::from_secs(days * 24 * 60 * 60)));
+        match RotatingFile::new(&log_directory, &log_name, "log", config.max_file_size){
+            Ok(file) => {
+                let file_drain = FullFormat::new(PlainSyncDecorator::new(file)).build().fuse();
+                let file_config = config.clone();
+                let file_drain = Filter::new(file_drain, move |record : &Record| file_config.accepts(record, file_config.file_level)).fuse();
+                drain = Box::new(Duplicate::new(drain, Self::async_drain(file_drain)).fuse());
+            }
+            //A read only location, like a kiosk image, should not keep the engine from starting.
+            Err(error) => {errors.push(format!("Failed to create a log file in {:?}, logging to the terminal only, {}", log_directory, error))}
+        }
+        if config.json{
+            match RotatingFile::new(&log_directory, &log_name, "jsonl", config.max_file_size){
+                Ok(file) => {
+                    let json_drain = slog_json::Json::new(file).add_default_keys().add_key_value(o!("module" => FnValue(|record : &Record| record.module()))).build().fuse();
+                    let json_config = config.clone();
+                    let json_drain = Filter::new(json_drain, move |record : &Record| json_config.accepts(record, json_config.file_level)).fuse();
+                    drain = Box::new(Duplicate::new(drain, Self::async_drain(json_drain)).fuse());
+                }
+                Err(error) => {errors.push(format!("Failed to create a JSON log file in {:?}, {}", log_directory, error))}
+            }
+        }
+        let logger = Logger::root(drain, o!());
+        for error in errors{warn!(logger, "{}", error)}
+        if let Some(error) = config_error{warn!(logger, "Invalid config config://logging.toml, {}", error)}
+        return logger;
+    }
+    fn async_drain<D : Drain<Ok = (), Err = Never> + Send + 'static>(drain : D) -> Arc<FlushableAsync>{
+        return FlushableAsync::new(drain);
+    }
+    ///Reads logging.toml, writing out the defaults when it is missing.
+    fn logging_config(&self) -> (LoggingConfig, Option<String>){
+        return match self.vfs.read_with("config://logging.toml", |data| config::parse::<LoggingConfig>(data, Format::Toml)){
+            Ok(Ok(parsed)) => {(parsed.file, None)}
+            Ok(Err(error)) => {(LoggingConfig::default(), Some(error.to_string()))}
+            Err(VfsError::NotFound(_)) => {
+                let config = LoggingConfig::default();
+                //Writing out the defaults is only a convenience, a read only config directory is fine.
+                let _ = config::serialize(&config, Format::Toml).map(|data| self.vfs.write("config://logging.toml", &data));
+                (config, None)
+            }
+            Err(error) => {(LoggingConfig::default(), Some(error.to_string()))}
+        }
+    }
+    ///The directory holding the log files and crash reports, as configured by logging.toml.
+    pub fn logs_directory(&self) -> PathBuf{
+        return self.logs_directory_of(&self.logging_config().0);
+    }
+    fn logs_directory_of(&self, config : &LoggingConfig) -> PathBuf{
+        return match config.directory{
+            LogDirectory::Config => {self.config_directory.join("logs")}
+            LogDirectory::Cache => {self.cache_directory.join("logs")}
+        }
+    }
+    fn get_path(&self, name : &str, file_type : FileType, format : Format) -> String{
+        let extension = format.extension();
         return match file_type{
-            FileType::Config => {self.config_directory.join(format!("./{}.toml",name))}
-            FileType::Cache => {self.cache_directory.join(format!("./{}.cache", name))}
+            FileType::Config => {format!("config://{}.{}", name, extension)}
+            FileType::Cache => {format!("cache://{}.{}", name, extension)}
+            FileType::Scene => {format!("assets://scenes/{}.{}", name, extension)}
+            FileType::Save => {format!("data://saves/{}.{}", name, extension)}
         }
     }
+    ///Loads the file in the format of its type, migrating older versions, or returns None when it is missing or invalid.
     pub fn load_file<T>(&self, logger : &Logger, name : &str, file_type : FileType) -> Option<T>
-    where T : DeserializeOwned{
-        let path = self.get_path(name, file_type);
-        return if !path.exists() { None } else {
-            let file_data = std::fs::read(path.clone()).unwrap();
-            match toml::from_slice(&file_data) {
-                Ok(config) => { Some(config) }
-                Err(error) => {
-                    warn!(logger, "Invalid config {:?}, {}", path, error);
-                    None
+    where T : Versioned + DeserializeOwned{
+        return self.load_file_as(logger, name, file_type, file_type.format());
+    }
+    pub fn load_file_as<T>(&self, logger : &Logger, name : &str, file_type : FileType, format : Format) -> Option<T>
+    where T : Versioned + DeserializeOwned{
+        return self.read_file(logger, &self.get_path(name, file_type, format), format).ok();
+    }
+    ///Like `load_file`, but says why the file could not be loaded.
+    pub fn try_load_file<T>(&self, logger : &Logger, name : &str, file_type : FileType) -> Result<T, String>
+    where T : Versioned + DeserializeOwned{
+        let format = file_type.format();
+        return self.read_file(logger, &self.get_path(name, file_type, format), format).map_err(LoadFailure::into_message);
+    }
+    ///Loads the file or writes out the defaults when it is missing.
+    ///An invalid file is kept as `.bak` before it is replaced, so a typo does not lose the rest of the settings.
+    pub fn load_file_or_default<T>(&self, logger : &Logger, name : &str, file_type : FileType) -> T
+    where T : Versioned + Serialize + DeserializeOwned + Default{
+        let format = file_type.format();
+        let path = self.get_path(name, file_type, format);
+        let data = T::default();
+        match self.read_file(logger, &path, format){
+            Ok(file) => {return file}
+            //A file that exists but cannot be read is left alone.
+            Err(LoadFailure::Unreadable(_)) => {return data}
+            Err(LoadFailure::Invalid(_)) => {
+                let backup = format!("{}.bak", path);
+                match self.vfs.read(&path).and_then(|broken| self.vfs.write(&backup, &broken)){
+                    Ok(()) => {warn!(logger, "Kept the invalid file {} as {}, using the defaults.", path, backup)}
+                    Err(error) => {
+                        warn!(logger, "Failed to keep the invalid file {} as {}, using the defaults without replacing it, {}.", path, backup, error);
+                        return data;
+                    }
                 }
             }
+            Err(LoadFailure::Missing(_) | LoadFailure::Stale(_)) => {}
         }
+        if let Err(error) = self.save_file(name, file_type, &data){warn!(logger, "Failed to write the defaults, {}.", error)}
+        return data;
     }
-    pub fn load_file_or_default<T>(&self, logger : &Logger, name : &str, file_type : FileType) -> T
-    where T : Serialize + DeserializeOwned + Default{
-        return match self.load_file(logger, name, file_type){
-            Some(file) => {file}
-            None => {
-                let data = T::default();
-                self.save_file(name, file_type, &data);
-                data
+    ///Replaces the file in the format of its type atomically, a crash or a full disk leaves either the old or the new version.
+    pub fn save_file<T>(&self, name : &str, file_type : FileType, file : &T) -> Result<(), SaveError>
+    where T : Versioned + Serialize{
+        return self.save_file_as(name, file_type, file_type.format(), file);
+    }
+    pub fn save_file_as<T>(&self, name : &str, file_type : FileType, format : Format, file : &T) -> Result<(), SaveError>
+    where T : Versioned + Serialize{
+        let path = self.get_path(name, file_type, format);
+        let data = config::serialize(file, format).map_err(|error| SaveError::Serialize(path.clone(), error))?;
+        return self.vfs.write(&path, &data).map_err(SaveError::Write);
+    }
+    fn read_file<T>(&self, logger : &Logger, path : &str, format : Format) -> Result<T, LoadFailure>
+    where T : Versioned + DeserializeOwned{
+        return match self.vfs.read_with(path, |data| config::parse::<T>(data, format)){
+            Ok(Ok(parsed)) => {
+                if parsed.version > T::VERSION{warn!(logger, "The file {} has the newer version {}, expected {}, newer fields are dropped.", path, parsed.version, T::VERSION)}
+                else if parsed.version < T::VERSION{info!(logger, "Migrated {} from version {} to {}.", path, parsed.version, T::VERSION)}
+                if parsed.edited{debug!(logger, "The file {} was edited by hand.", path)}
+                Ok(parsed.file)
+            }
+            //Caches of an older engine are expected after an update and simply rebuilt.
+            Ok(Err(ParseError::Version(found, expected))) if format == Format::Binary => {
+                info!(logger, "Discarding {} of version {}, expected {}.", path, found, expected);
+                Err(LoadFailure::Stale(ParseError::Version(found, expected).to_string()))
             }
+            Ok(Err(error)) => {
+                warn!(logger, "Invalid file {}, {}", path, error);
+                Err(LoadFailure::Invalid(format!("invalid file {}, {}", path, error)))
+            }
+            Err(error @ VfsError::NotFound(_)) => {Err(LoadFailure::Missing(error.to_string()))}
+            Err(error) => {
+                warn!(logger, "Failed to read {}, {}", path, error);
+                Err(LoadFailure::Unreadable(error.to_string()))
+            }
+        }
+    }
+}
+#[derive(Clone, PartialEq, Debug)]
+pub enum SaveError{
+    ///The value could not be stored in the format of the file, with the path and error.
+    Serialize(String, String),
+    Write(VfsError),
+}
+impl Display for SaveError{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return match self{
+            SaveError::Serialize(path, error) => {write!(f, "failed to serialize {}, {}", path, error)}
+            SaveError::Write(error) => {write!(f, "{}", error)}
+        }
+    }
+}
+impl std::error::Error for SaveError{}
+///Why a file could not be loaded, with the message, the errors are logged where they occur.
+enum LoadFailure{
+    Missing(String),
+    ///A binary file of another version, replaced without keeping it.
+    Stale(String),
+    Unreadable(String),
+    Invalid(String),
+}
+impl LoadFailure{
+    fn into_message(self) -> String{
+        return match self{
+            LoadFailure::Missing(message) | LoadFailure::Stale(message) | LoadFailure::Unreadable(message) | LoadFailure::Invalid(message) => {message}
         }
     }
-    pub fn save_file<T>(&self, name : &str, file_type : FileType, file : &T)
-    where T : Serialize{
-        let path = self.get_path(name, file_type);
-        if !path.parent().unwrap().exists(){std::fs::create_dir_all(path.parent().unwrap()).unwrap()}
-        let data = toml::to_vec(file).unwrap();
-        std::fs::write(path, data).unwrap();
+}
+///The message of a panic payload, which is a string unless the panic passed another value.
+pub fn panic_message(payload : &(dyn Any + Send)) -> String{
+    return match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()){
+        (Some(message), _) => {message.to_string()}
+        (_, Some(message)) => {message.clone()}
+        _ => {"a panic without a message".to_owned()}
     }
 }
 #[derive(Copy, Clone)]
 pub enum FileType{
     Config,
     Cache,
-}
\ No newline at end of file
+    Scene,
+    Save,
+}
+impl FileType{
+    ///The format files of the type are stored in unless another one is given, binary for caches and TOML for everything else.
+    pub fn format(&self) -> Format{
+        return match self{
+            FileType::Cache => {Format::Binary}
+            FileType::Config | FileType::Scene | FileType::Save => {Format::Toml}
+        }
+    }
+}
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn flags_override_the_directories(){
+        let arguments = ["--portable", "--config-dir", "config", "--cache-dir=cache", "--unknown", "value", "--data-dir", "data", "--assets-dir=assets"];
+        let options = PathOptions::from_args(arguments.iter().map(OsString::from));
+        assert!(options.portable);
+        assert_eq!(options.config_directory, Some(PathBuf::from("config")));
+        assert_eq!(options.cache_directory, Some(PathBuf::from("cache")));
+        assert_eq!(options.data_directory, Some(PathBuf::from("data")));
+        assert_eq!(options.assets_directory, Some(PathBuf::from("assets")));
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::{Duration, SystemTime};
+use serde_derive::{Deserialize, Serialize};
+use slog::{Drain, Level, Never, OwnedKVList, Record};
+use slog_async::{Async, AsyncGuard};
+use crate::config::Versioned;
+
+///Every async drain created by the path managers, so they can be flushed from a panic hook.
+static DRAINS : Mutex<Vec<Arc<FlushableAsync>>> = Mutex::new(Vec::new());
+
+///Writes out every queued record, for a process about to go down.
+///The drains keep running, records logged afterwards are written as usual.
+pub fn flush_logs(){
+    let drains = DRAINS.lock().unwrap_or_else(PoisonError::into_inner).clone();
+    for drain in drains.iter(){drain.restart()}
+}
+
+type BoxedDrain = Box<dyn Drain<Ok = (), Err = Never> + Send>;
+
+///The drain behind an async worker, shared so a restarted worker writes to the same file.
+struct SharedDrain(Arc<Mutex<BoxedDrain>>);
+impl Drain for SharedDrain{
+    type Ok = ();
+    type Err = Never;
+    fn log(&self, record : &Record, values : &OwnedKVList) -> Result<(), Never>{
+        return self.0.lock().unwrap_or_else(PoisonError::into_inner).log(record, values);
+    }
+}
+
+///An async drain that is flushed by stopping its worker and starting a new one on the same drain.
+pub(crate) struct FlushableAsync{
+    drain : Arc<Mutex<BoxedDrain>>,
+    ///Dropping the guard waits until the worker wrote every queued record.
+    worker : RwLock<(Async, AsyncGuard)>,
+}
+impl FlushableAsync{
+    pub(crate) fn new<D : Drain<Ok = (), Err = Never> + Send + 'static>(drain : D) -> Arc<Self>{
+        let drain : Arc<Mutex<BoxedDrain>> = Arc::new(Mutex::new(Box::new(drain)));
+        let worker = RwLock::new(Async::new(SharedDrain(drain.clone())).build_with_guard());
+        let flushable = Arc::new(Self{drain, worker});
+        DRAINS.lock().unwrap_or_else(PoisonError::into_inner).push(flushable.clone());
+        return flushable;
+    }
+    fn restart(&self){
+        //Holding the lock makes records logged meanwhile wait for the new worker, so they stay in order.
+        let mut worker = self.worker.write().unwrap_or_else(PoisonError::into_inner);
+        let (_, guard) = std::mem::replace(&mut *worker, Async::new(SharedDrain(self.drain.clone())).build_with_guard());
+        drop(guard);
+    }
+}
+impl Drain for FlushableAsync{
+    type Ok = ();
+    type Err = Never;
+    fn log(&self, record : &Record, values : &OwnedKVList) -> Result<(), Never>{
+        //A full queue or a stopped worker only loses the record.
+        let _ = self.worker.read().unwrap_or_else(PoisonError::into_inner).0.log(record, values);
+        return Ok(());
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel{
+    Critical,
+    Error,
+    Warning,
+    Info,
+    Debug,
+    Trace,
+}
+impl LogLevel{
+    pub fn level(&self) -> Level{
+        return match self{
+            LogLevel::Critical => {Level::Critical}
+            LogLevel::Error => {Level::Error}
+            LogLevel::Warning => {Level::Warning}
+            LogLevel::Info => {Level::Info}
+            LogLevel::Debug => {Level::Debug}
+            LogLevel::Trace => {Level::Trace}
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogDirectory{
+    Config,
+    Cache,
+}
+
+///The contents of logging.toml.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig{
+    pub terminal_level : LogLevel,
+    pub file_level : LogLevel,
+    ///Whether the logs directory is placed in the config or the cache directory.
+    pub directory : LogDirectory,
+    ///The most log files kept, the oldest are removed on startup.
+    pub max_files : Option<usize>,
+    ///Log files older than this many days are removed on startup.
+    pub max_age_days : Option<u64>,
+    ///A log file growing past this many bytes is continued in a new file.
+    pub max_file_size : Option<u64>,
+    ///Also writes the file level records as JSON lines to a .jsonl file, for log ingestion tools.
+    pub json : bool,
+    ///The minimum level of records from a module and its submodules, like `omage_renderer = "warning"`, applied before both drains.
+    pub modules : BTreeMap<String, LogLevel>,
+}
+impl Default for LoggingConfig{
+    fn default() -> Self {
+        return Self{
+            terminal_level : LogLevel::Info,
+            file_level : LogLevel::Debug,
+            directory : LogDirectory::Config,
+            max_files : Some(20),
+            max_age_days : Some(14),
+            max_file_size : Some(16 * 1024 * 1024),
+            json : false,
+            modules : BTreeMap::new(),
+        }
+    }
+}
+impl Versioned for LoggingConfig{}
+impl LoggingConfig{
+    ///Whether the record passes the module filters and the minimum level of a drain.
+    pub fn accepts(&self, record : &Record, minimum : LogLevel) -> bool{
+        let module = record.module();
+        let filter = self.modules.iter()
+            .filter(|(name, _)| module == name.as_str() || module.strip_prefix(name.as_str()).is_some_and(|rest| rest.starts_with("::")))
+            .max_by_key(|(name, _)| name.len());
+        if let Some((_, level)) = filter{
+            if !record.level().is_at_least(level.level()){return false}
+        }
+        return record.level().is_at_least(minimum.level());
+    }
+}
+
+///The extensions of the files written by the log drains, other files in the logs directory like crash reports are never pruned.
+const LOG_EXTENSIONS : [&str; 2] = ["log", "jsonl"];
+
+///Removes the log files in the logs directory that are too old, then the oldest until `keep` log files are left.
+///Files that cannot be read or removed are skipped, the returned messages describe them.
+pub fn prune_logs(directory : &Path, keep : Option<usize>, max_age : Option<Duration>) -> Vec<String>{
+    let mut errors = vec![];
+    let entries = match std::fs::read_dir(directory){
+        Ok(entries) => {entries}
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {return errors}
+        Err(error) => {return vec![format!("Failed to list the logs in {:?}, {}", directory, error)]}
+    };
+    let mut files : Vec<(SystemTime, PathBuf)> = vec![];
+    for entry in entries.flatten(){
+        let path = entry.path();
+        if !path.extension().is_some_and(|extension| LOG_EXTENSIONS.iter().any(|log| extension == *log)){continue;}
+        match entry.metadata().and_then(|metadata| Ok((metadata.is_file(), metadata.modified()?))){
+            Ok((true, modified)) => {files.push((modified, path))}
+            Ok((false, _)) => {}
+            Err(error) => {errors.push(format!("Failed to read the log file {:?}, {}", path, error))}
+        }
+    }
+    files.sort();
+    let now = SystemTime::now();
+    let expired = files.iter().take_while(|(modified, _)| max_age.is_some_and(|max_age| now.duration_since(*modified).unwrap_or_default() > max_age)).count();
+    let excess = keep.map_or(0, |keep| files.len().saturating_sub(keep));
+    for (_, path) in files.iter().take(expired.max(excess)){
+        if let Err(error) = std::fs::remove_file(path){errors.push(format!("Failed to remove the old log file {:?}, {}", path, error))}
+    }
+    return errors;
+}
+
+///A log file that continues in `<name>.1.log`, `<name>.2.log` and so on once it grew past the maximum size.
+pub struct RotatingFile{
+    directory : PathBuf,
+    name : String,
+    extension : String,
+    max_size : Option<u64>,
+    index : u32,
+    written : u64,
+    file : File,
+}
+impl RotatingFile{
+    pub fn new(directory : &Path, name : &str, extension : &str, max_size : Option<u64>) -> std::io::Result<Self>{
+        std::fs::create_dir_all(directory)?;
+        let file = Self::open(&directory.join(format!("{}.{}", name, extension)))?;
+        return Ok(Self{directory : directory.to_path_buf(), name : name.to_owned(), extension : extension.to_owned(), max_size, index : 0, written : 0, file});
+    }
+    fn open(path : &Path) -> std::io::Result<File>{
+        return OpenOptions::new().create(true).write(true).truncate(true).open(path);
+    }
+    fn rotate(&mut self) -> std::io::Result<()>{
+        self.file.flush()?;
+        self.index += 1;
+        self.file = Self::open(&self.directory.join(format!("{}.{}.{}", self.name, self.index, self.extension)))?;
+        self.written = 0;
+        return Ok(());
+    }
+}
+impl Write for RotatingFile{
+    fn write(&mut self, buffer : &[u8]) -> std::io::Result<usize>{
+        //The sync decorator writes every record at once, so checking before a write only rotates between records.
+        if self.max_size.is_some_and(|max_size| self.written >= max_size) && self.written > 0{self.rotate()?}
+        let written = self.file.write(buffer)?;
+        self.written += written as u64;
+        return Ok(written);
+    }
+    fn flush(&mut self) -> std::io::Result<()>{
+        return self.file.flush();
+    }
+}
@@ -0,0 +1,298 @@
+use serde_json::{Map, Number, Value};
+
+///Parses RON without knowing its type, keeping what `ron::Value` drops, like the names of enum variants.
+///Unit variants become strings and variants with data objects with the variant as the only key, the way serde reads them back.
+///Structs are expected without their names, as they are saved, `None` and `()` become null and `Some` is unwrapped.
+pub fn parse(text : &str) -> Result<Value, String>{
+    let mut reader = Reader{text, position : 0};
+    reader.skip_attributes()?;
+    let value = reader.value()?;
+    reader.skip_whitespace()?;
+    if reader.position != text.len(){return Err(reader.error("trailing characters"))}
+    return Ok(value);
+}
+
+struct Reader<'a>{
+    text : &'a str,
+    position : usize,
+}
+impl<'a> Reader<'a>{
+    fn rest(&self) -> &'a str{
+        return &self.text[self.position..];
+    }
+    fn peek(&self) -> Option<char>{
+        return self.rest().chars().next();
+    }
+    fn error(&self, message : &str) -> String{
+        let line = self.text[..self.position].matches('\n').count() + 1;
+        return format!("{} at line {}", message, line);
+    }
+    fn skip_whitespace(&mut self) -> Result<(), String>{
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.position += rest.len() - trimmed.len();
+            if trimmed.starts_with("//"){
+                self.position += trimmed.find('\n').unwrap_or(trimmed.len());
+            }
+            else if trimmed.starts_with("/*"){
+                let end = trimmed.find("*/").ok_or_else(|| self.error("unterminated comment"))?;
+                self.position += end + 2;
+            }
+            else{
+                return Ok(());
+            }
+        }
+    }
+    ///Skips `#![enable(...)]` attributes at the start of the file.
+    fn skip_attributes(&mut self) -> Result<(), String>{
+        self.skip_whitespace()?;
+        while self.rest().starts_with("#!["){
+            let end = self.rest().find(']').ok_or_else(|| self.error("unterminated attribute"))?;
+            self.position += end + 1;
+            self.skip_whitespace()?;
+        }
+        return Ok(());
+    }
+    ///Consumes the character after whitespace if it is the expected one.
+    fn consume(&mut self, expected : char) -> Result<bool, String>{
+        self.skip_whitespace()?;
+        if self.peek() == Some(expected){
+            self.position += expected.len_utf8();
+            return Ok(true);
+        }
+        return Ok(false);
+    }
+    fn expect(&mut self, expected : char) -> Result<(), String>{
+        if !self.consume(expected)?{return Err(self.error(&format!("expected {}", expected)))}
+        return Ok(());
+    }
+    fn value(&mut self) -> Result<Value, String>{
+        self.skip_whitespace()?;
+        return match self.peek(){
+            Some('"') => {Ok(Value::String(self.string()?))}
+            Some('\'') => {Ok(Value::String(self.character()?))}
+            Some('[') => {
+                self.position += 1;
+                Ok(Value::Array(self.sequence(']')?))
+            }
+            Some('{') => {
+                self.position += 1;
+                self.map()
+            }
+            Some('(') => {
+                self.position += 1;
+                self.parenthesized()
+            }
+            Some('r') if self.rest().starts_with("r\"") || self.rest().starts_with("r#\"") || self.rest().starts_with("r##") => {Ok(Value::String(self.raw_string()?))}
+            Some(character) if character.is_ascii_digit() || character == '-' || character == '+' || character == '.' => {self.number()}
+            Some(_) => {
+                let identifier = self.identifier()?;
+                match identifier.as_str(){
+                    "true" => {Ok(Value::Bool(true))}
+                    "false" => {Ok(Value::Bool(false))}
+                    "None" => {Ok(Value::Null)}
+                    "Some" => {
+                        self.expect('(')?;
+                        let value = self.value()?;
+                        self.consume(',')?;
+                        self.expect(')')?;
+                        Ok(value)
+                    }
+                    "inf" | "NaN" => {Err(self.error("non-finite numbers cannot be migrated"))}
+                    _ if self.consume('(')? => {
+                        let data = self.parenthesized()?;
+                        let mut variant = Map::new();
+                        variant.insert(identifier, data);
+                        Ok(Value::Object(variant))
+                    }
+                    _ => {Ok(Value::String(identifier))}
+                }
+            }
+            None => {Err(self.error("unexpected end of file"))}
+        }
+    }
+    ///Reads the rest of `(...)`, a struct becomes an object, a single value stays itself and several an array.
+    fn parenthesized(&mut self) -> Result<Value, String>{
+        if self.consume(')')?{return Ok(Value::Null)}
+        if self.is_field()?{return self.fields(')')}
+        let mut values = self.sequence(')')?;
+        return Ok(if values.len() == 1 {values.remove(0)} else {Value::Array(values)});
+    }
+    ///Whether the next item is a `name :` field.
+    fn is_field(&mut self) -> Result<bool, String>{
+        self.skip_whitespace()?;
+        let start = self.position;
+        let is_field = self.peek().is_some_and(|character| character.is_alphabetic() || character == '_') && self.identifier().is_ok() && self.consume(':')?;
+        self.position = start;
+        return Ok(is_field);
+    }
+    fn sequence(&mut self, end : char) -> Result<Vec<Value>, String>{
+        let mut values = vec![];
+        loop {
+            if self.consume(end)?{return Ok(values)}
+            values.push(self.value()?);
+            if !self.consume(',')?{
+                self.expect(end)?;
+                return Ok(values);
+            }
+        }
+    }
+    fn fields(&mut self, end : char) -> Result<Value, String>{
+        let mut fields = Map::new();
+        loop {
+            if self.consume(end)?{return Ok(Value::Object(fields))}
+            self.skip_whitespace()?;
+            let name = self.identifier()?;
+            self.expect(':')?;
+            fields.insert(name, self.value()?);
+            if !self.consume(',')?{
+                self.expect(end)?;
+                return Ok(Value::Object(fields));
+            }
+        }
+    }
+    ///Reads the rest of `{...}`, keys are turned into strings as JSON only has string keys.
+    fn map(&mut self) -> Result<Value, String>{
+        let mut entries = Map::new();
+        loop {
+            if self.consume('}')?{return Ok(Value::Object(entries))}
+            let key = match self.value()?{
+                Value::String(key) => {key}
+                Value::Number(key) => {key.to_string()}
+                Value::Bool(key) => {key.to_string()}
+                _ => {return Err(self.error("only strings, numbers and booleans can be migrated as map keys"))}
+            };
+            self.expect(':')?;
+            entries.insert(key, self.value()?);
+            if !self.consume(',')?{
+                self.expect('}')?;
+                return Ok(Value::Object(entries));
+            }
+        }
+    }
+    ///Reads an identifier, raw identifiers like `r#1.1` may hold more characters.
+    fn identifier(&mut self) -> Result<String, String>{
+        let rest = self.rest();
+        let (raw, rest) = match rest.strip_prefix("r#"){
+            Some(rest) => {(true, rest)}
+            None => {(false, rest)}
+        };
+        let length = rest.find(|character : char| !(character.is_alphanumeric() || character == '_' || raw && matches!(character, '.' | '+' | '-'))).unwrap_or(rest.len());
+        if length == 0{return Err(self.error("expected a value"))}
+        self.position += length + if raw {2} else {0};
+        return Ok(rest[..length].to_owned());
+    }
+    fn number(&mut self) -> Result<Value, String>{
+        let rest = self.rest();
+        let length = rest.find(|character : char| !(character.is_ascii_alphanumeric() || matches!(character, '.' | '+' | '-' | '_'))).unwrap_or(rest.len());
+        self.position += length;
+        let text = rest[..length].replace('_', "");
+        let (negative, digits) = match text.strip_prefix('-'){
+            Some(digits) => {(true, digits)}
+            None => {(false, text.strip_prefix('+').unwrap_or(&text))}
+        };
+        let radix = match digits.get(..2){
+            Some("0x") => {Some(16)}
+            Some("0o") => {Some(8)}
+            Some("0b") => {Some(2)}
+            _ => {None}
+        };
+        let invalid = || self.error(&format!("invalid number {}", text));
+        if let Some(radix) = radix{
+            let value = i128::from_str_radix(&digits[2..], radix).map_err(|_| invalid())?;
+            let value = if negative {-value} else {value};
+            return i64::try_from(value).map(Number::from).or_else(|_| u64::try_from(value).map(Number::from)).map(Value::Number).map_err(|_| invalid());
+        }
+        if let Ok(value) = text.trim_start_matches('+').parse::<i64>(){return Ok(Value::Number(value.into()))}
+        if let Ok(value) = text.trim_start_matches('+').parse::<u64>(){return Ok(Value::Number(value.into()))}
+        let value = text.parse::<f64>().map_err(|_| invalid())?;
+        return Number::from_f64(value).map(Value::Number).ok_or_else(|| self.error("non-finite numbers cannot be migrated"));
+    }
+    fn string(&mut self) -> Result<String, String>{
+        self.position += 1;
+        let mut string = String::new();
+        let mut characters = self.rest().char_indices();
+        while let Some((index, character)) = characters.next(){
+            match character{
+                '"' => {
+                    self.position += index + 1;
+                    return Ok(string);
+                }
+                '\\' => {string.push(self.escape(&mut characters)?)}
+                character => {string.push(character)}
+            }
+        }
+        return Err(self.error("unterminated string"));
+    }
+    fn character(&mut self) -> Result<String, String>{
+        self.position += 1;
+        let mut characters = self.rest().char_indices();
+        let character = match characters.next(){
+            Some((_, '\\')) => {self.escape(&mut characters)?}
+            Some((_, character)) => {character}
+            None => {return Err(self.error("unterminated character"))}
+        };
+        return match characters.next(){
+            Some((index, '\'')) => {
+                self.position += index + 1;
+                Ok(character.to_string())
+            }
+            _ => {Err(self.error("unterminated character"))}
+        }
+    }
+    fn escape(&self, characters : &mut std::str::CharIndices) -> Result<char, String>{
+        return match characters.next().map(|(_, character)| character){
+            Some('n') => {Ok('\n')}
+            Some('r') => {Ok('\r')}
+            Some('t') => {Ok('\t')}
+            Some('0') => {Ok('\0')}
+            Some('b') => {Ok('\u{8}')}
+            Some('f') => {Ok('\u{c}')}
+            Some('u') => {
+                let digits : String = characters.by_ref().take(4).map(|(_, character)| character).collect();
+                u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32).ok_or_else(|| self.error("invalid unicode escape"))
+            }
+            Some(character @ ('"' | '\'' | '\\' | '/')) => {Ok(character)}
+            _ => {Err(self.error("invalid escape"))}
+        }
+    }
+    fn raw_string(&mut self) -> Result<String, String>{
+        let rest = &self.rest()[1..];
+        let hashes = rest.len() - rest.trim_start_matches('#').len();
+        let rest = &rest[hashes..];
+        if !rest.starts_with('"'){return Err(self.error("invalid raw string"))}
+        let end = format!("\"{}", "#".repeat(hashes));
+        let length = rest[1..].find(&end).ok_or_else(|| self.error("unterminated raw string"))?;
+        self.position += 1 + hashes + 1 + length + end.len();
+        return Ok(rest[1..1 + length].to_owned());
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use serde_json::json;
+    use super::*;
+
+    #[test]
+    fn enum_variants_keep_their_names(){
+        let text = "#![enable(implicit_some)]\n(\n    version: r#1.1, // the cap\n    mode: Windowed,\n    size: Fixed(800, 600),\n    button: Key(A),\n    shape: Circle(radius: 1.5),\n    nothing: (),\n)";
+        let value = parse(text).unwrap();
+        assert_eq!(value, json!({"version" : "1.1", "mode" : "Windowed", "size" : {"Fixed" : [800, 600]}, "button" : {"Key" : "A"}, "shape" : {"Circle" : {"radius" : 1.5}}, "nothing" : null}));
+    }
+    #[test]
+    fn scalars_lists_and_maps_are_read(){
+        let text = r##"(name: "a \"quoted\" é", raw: r#"raw "text""#, letter: 'x', numbers: [1, -2, 0x10, 1_000, 2.5e1, +3], options: [Some(1), None], map: {"key": 1, 2: true, false: 'c'}, tuple: (1, "two"), nested: [[], {}])"##;
+        let value = parse(text).unwrap();
+        assert_eq!(value, json!({
+            "name" : "a \"quoted\" é", "raw" : "raw \"text\"", "letter" : "x", "numbers" : [1, -2, 16, 1000, 25.0, 3], "options" : [1, null],
+            "map" : {"key" : 1, "2" : true, "false" : "c"}, "tuple" : [1, "two"], "nested" : [[], {}],
+        }));
+    }
+    #[test]
+    fn invalid_files_are_rejected(){
+        for text in ["(a: 1", "(a: \"b)", "[1, 2] 3", "(a: inf)", "{[1]: 2}", "/* open"]{
+            assert!(parse(text).is_err(), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::archive::{Archive, ArchiveError};
+
+///The priorities of the default mounts, a source hides the files of the sources below it.
+pub mod priority{
+    pub const BASE : i32 = 0;
+    pub const ARCHIVE : i32 = 10;
+    pub const MODS : i32 = 20;
+    ///Files the application writes under `assets://`, like saved scenes, kept in the data directory so they also work with packed assets.
+    pub const USER : i32 = 25;
+    pub const OVERRIDE : i32 = 30;
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum VfsError{
+    ///The path has no `scheme://` prefix or leaves its mount through `..`.
+    InvalidPath(String),
+    NotFound(String),
+    ///No source of the scheme accepts writes.
+    ReadOnly(String),
+    Io(String, String),
+}
+impl Display for VfsError{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return match self{
+            VfsError::InvalidPath(path) => {write!(f, "invalid path {}", path)}
+            VfsError::NotFound(path) => {write!(f, "no mounted source has {}", path)}
+            VfsError::ReadOnly(path) => {write!(f, "no mounted source can write {}", path)}
+            VfsError::Io(path, error) => {write!(f, "failed to access {}, {}", path, error)}
+        }
+    }
+}
+impl std::error::Error for VfsError{}
+
+///A place files are read from, paths are relative and separated by forward slashes.
+pub trait Source : Send + Sync{
+    fn read(&self, path : &str) -> Result<Cow<'_, [u8]>, VfsError>;
+    fn exists(&self, path : &str) -> bool;
+    fn write(&self, path : &str, _data : &[u8]) -> Result<(), VfsError>{
+        return Err(VfsError::ReadOnly(path.to_owned()));
+    }
+    ///The directory on disk holding the files, used to watch them for changes.
+    fn directory(&self) -> Option<&Path>{
+        return None;
+    }
+}
+
+///Files below a directory on disk, writes replace files atomically.
+pub struct DirectorySource{
+    root : PathBuf,
+    writable : bool,
+    backups : usize,
+}
+impl DirectorySource{
+    pub fn new(root : PathBuf) -> Self{
+        return Self{root, writable : true, backups : 0};
+    }
+    pub fn read_only(root : PathBuf) -> Self{
+        return Self{root, writable : false, backups : 0};
+    }
+    ///Keeps the previous versions of overwritten files as `<file>.1.bak`, `<file>.2.bak` and so on, the newest first.
+    pub fn with_backups(mut self, backups : usize) -> Self{
+        self.backups = backups;
+        return self;
+    }
+    ///Shifts the backups of the file by one and copies the current version into the first.
+    fn back_up(&self, path : &Path) -> std::io::Result<()>{
+        if self.backups == 0 || !path.is_file(){return Ok(())}
+        let backup = |index : usize| {
+            let mut name = path.as_os_str().to_owned();
+            name.push(format!(".{}.bak", index));
+            PathBuf::from(name)
+        };
+        for index in (1..self.backups).rev(){
+            match std::fs::rename(backup(index), backup(index + 1)){
+                Err(error) if error.kind() != std::io::ErrorKind::NotFound => {return Err(error)}
+                _ => {}
+            }
+        }
+        //Copying keeps the file in place, so a crash before the new version is renamed over it leaves the old one.
+        std::fs::copy(path, backup(1))?;
+        return Ok(());
+    }
+    ///Writes the data to a temporary file beside the target, flushes it to disk and renames it over the target.
+    ///Every write gets its own temporary file, so concurrent saves of the same file cannot clobber each other's.
+    fn write_atomic(path : &Path, data : &[u8]) -> std::io::Result<()>{
+        static NEXT_TEMPORARY : AtomicU64 = AtomicU64::new(0);
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(format!(".{}.{}.tmp", std::process::id(), NEXT_TEMPORARY.fetch_add(1, Ordering::Relaxed)));
+        let temporary = PathBuf::from(temporary);
+        let result = File::create(&temporary)
+            .and_then(|mut file| file.write_all(data).and_then(|_| file.sync_all()))
+            .and_then(|_| std::fs::rename(&temporary, path));
+        if result.is_err(){let _ = std::fs::remove_file(&temporary);}
+        result?;
+        //The rename itself only survives a power loss once the directory is flushed, which is not possible on Windows.
+        #[cfg(unix)]
+        if let Some(parent) = path.parent(){File::open(parent)?.sync_all()?}
+        return Ok(());
+    }
+}
+impl Source for DirectorySource{
+    fn read(&self, path : &str) -> Result<Cow<'_, [u8]>, VfsError>{
+        return match std::fs::read(self.root.join(path)){
+            Ok(data) => {Ok(Cow::Owned(data))}
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {Err(VfsError::NotFound(path.to_owned()))}
+            Err(error) => {Err(VfsError::Io(path.to_owned(), error.to_string()))}
+        }
+    }
+    fn exists(&self, path : &str) -> bool{
+        return self.root.join(path).is_file();
+    }
+    fn write(&self, path : &str, data : &[u8]) -> Result<(), VfsError>{
+        if !self.writable{return Err(VfsError::ReadOnly(path.to_owned()))}
+        let full_path = self.root.join(path);
+        let io_error = |error : std::io::Error| VfsError::Io(path.to_owned(), error.to_string());
+        if let Some(parent) = full_path.parent(){std::fs::create_dir_all(parent).map_err(io_error)?}
+        self.back_up(&full_path).map_err(io_error)?;
+        return Self::write_atomic(&full_path, data).map_err(io_error);
+    }
+    fn directory(&self) -> Option<&Path>{
+        return Some(&self.root);
+    }
+}
+
+impl Source for Archive{
+    fn read(&self, path : &str) -> Result<Cow<'_, [u8]>, VfsError>{
+        return Archive::read(self, path).map_err(|error| match error{
+            ArchiveError::Missing(path) => {VfsError::NotFound(path)}
+            error => {VfsError::Io(path.to_owned(), error.to_string())}
+        });
+    }
+    fn exists(&self, path : &str) -> bool{
+        return self.contains(path);
+    }
+}
+
+///Files kept in memory, for tests and generated content.
+#[derive(Default)]
+pub struct MemorySource{
+    files : RwLock<HashMap<String, Vec<u8>>>,
+}
+impl MemorySource{
+    pub fn new() -> Self{
+        return Self::default();
+    }
+    pub fn insert(&self, path : &str, data : Vec<u8>){
+        self.files.write().unwrap().insert(path.to_owned(), data);
+    }
+}
+impl Source for MemorySource{
+    fn read(&self, path : &str) -> Result<Cow<'_, [u8]>, VfsError>{
+        return self.files.read().unwrap().get(path).cloned().map(Cow::Owned).ok_or_else(|| VfsError::NotFound(path.to_owned()));
+    }
+    fn exists(&self, path : &str) -> bool{
+        return self.files.read().unwrap().contains_key(path);
+    }
+    fn write(&self, path : &str, data : &[u8]) -> Result<(), VfsError>{
+        self.insert(path, data.to_vec());
+        return Ok(());
+    }
+}
+
+struct Mount{
+    scheme : String,
+    priority : i32,
+    source : Arc<dyn Source>,
+}
+
+///Ordered stacks of sources mounted under schemes like `assets://`, shared between its clones.
+#[derive(Clone, Default)]
+pub struct Vfs{
+    mounts : Arc<RwLock<Vec<Mount>>>,
+}
+impl Vfs{
+    pub fn new() -> Self{
+        return Self::default();
+    }
+    ///Mounts the source above every source of the scheme with a lower or equal priority.
+    pub fn mount(&self, scheme : &str, source : Arc<dyn Source>, priority : i32){
+        let mut mounts = self.mounts.write().unwrap();
+        let index = mounts.iter().position(|mount| mount.priority > priority).unwrap_or(mounts.len());
+        mounts.insert(index, Mount{scheme : scheme.to_owned(), priority, source});
+    }
+    ///Removes every mount of the source.
+    pub fn unmount(&self, source : &Arc<dyn Source>){
+        self.mounts.write().unwrap().retain(|mount| !Arc::ptr_eq(&mount.source, source));
+    }
+    ///Reads the file from the highest source of the scheme that has it.
+    pub fn read(&self, path : &str) -> Result<Vec<u8>, VfsError>{
+        return self.read_with(path, <[u8]>::to_vec);
+    }
+    ///Passes the file to the function without copying it out of sources that can lend it, like archives.
+    pub fn read_with<R, F : FnOnce(&[u8]) -> R>(&self, path : &str, function : F) -> Result<R, VfsError>{
+        let (scheme, relative) = split(path)?;
+        for source in self.sources(scheme){
+            match source.read(relative){
+                Ok(data) => {return Ok(function(&data))}
+                Err(VfsError::NotFound(_)) => {}
+                Err(error) => {return Err(error)}
+            }
+        }
+        return Err(VfsError::NotFound(path.to_owned()));
+    }
+    pub fn exists(&self, path : &str) -> bool{
+        return split(path).map(|(scheme, relative)| self.sources(scheme).iter().any(|source| source.exists(relative))).unwrap_or(false);
+    }
+    ///Writes the file to the highest source of the scheme that accepts writes.
+    pub fn write(&self, path : &str, data : &[u8]) -> Result<(), VfsError>{
+        let (scheme, relative) = split(path)?;
+        for source in self.sources(scheme){
+            match source.write(relative, data){
+                Err(VfsError::ReadOnly(_)) => {}
+                result => {return result}
+            }
+        }
+        return Err(VfsError::ReadOnly(path.to_owned()));
+    }
+    ///The directories on disk mounted under the scheme, highest first.
+    pub fn directories(&self, scheme : &str) -> Vec<PathBuf>{
+        return self.sources(scheme).iter().filter_map(|source| source.directory().map(Path::to_path_buf)).collect();
+    }
+    fn sources(&self, scheme : &str) -> Vec<Arc<dyn Source>>{
+        return self.mounts.read().unwrap().iter().rev().filter(|mount| mount.scheme == scheme).map(|mount| mount.source.clone()).collect();
+    }
+}
+
+///Splits `scheme://relative/path` and rejects paths escaping their mount.
+fn split(path : &str) -> Result<(&str, &str), VfsError>{
+    let (scheme, relative) = path.split_once("://").ok_or_else(|| VfsError::InvalidPath(path.to_owned()))?;
+    if !Path::new(relative).components().all(|component| matches!(component, Component::Normal(_))){return Err(VfsError::InvalidPath(path.to_owned()))}
+    return Ok((scheme, relative));
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn memory(files : &[(&str, &str)]) -> Arc<MemorySource>{
+        let source = MemorySource::new();
+        for (path, data) in files{source.insert(path, data.as_bytes().to_vec())}
+        return Arc::new(source);
+    }
+    struct ReadOnlySource;
+    impl Source for ReadOnlySource{
+        fn read(&self, path : &str) -> Result<Cow<'_, [u8]>, VfsError>{
+            return Err(VfsError::NotFound(path.to_owned()));
+        }
+        fn exists(&self, _path : &str) -> bool{
+            return false;
+        }
+    }
+
+    #[test]
+    fn higher_priorities_hide_lower_ones(){
+        let vfs = Vfs::new();
+        vfs.mount("assets", memory(&[("a.txt", "override")]), priority::OVERRIDE);
+        vfs.mount("assets", memory(&[("a.txt", "base"), ("b.txt", "base")]), priority::BASE);
+        vfs.mount("assets", memory(&[("a.txt", "mods")]), priority::MODS);
+        assert_eq!(vfs.read("assets://a.txt").unwrap(), b"override");
+        assert_eq!(vfs.read("assets://b.txt").unwrap(), b"base");
+        assert_eq!(vfs.read("assets://c.txt"), Err(VfsError::NotFound("assets://c.txt".to_owned())));
+        assert!(!vfs.exists("config://a.txt"));
+    }
+    #[test]
+    fn equal_priorities_prefer_the_later_mount(){
+        let vfs = Vfs::new();
+        vfs.mount("assets", memory(&[("a.txt", "first")]), priority::MODS);
+        let second : Arc<dyn Source> = memory(&[("a.txt", "second")]);
+        vfs.mount("assets", second.clone(), priority::MODS);
+        assert_eq!(vfs.read("assets://a.txt").unwrap(), b"second");
+        vfs.unmount(&second);
+        assert_eq!(vfs.read("assets://a.txt").unwrap(), b"first");
+    }
+    #[test]
+    fn writes_fall_through_read_only_sources(){
+        let vfs = Vfs::new();
+        let writable = memory(&[]);
+        vfs.mount("assets", writable.clone(), priority::USER);
+        vfs.mount("assets", Arc::new(ReadOnlySource), priority::OVERRIDE);
+        vfs.write("assets://scenes/a.scene", b"scene").unwrap();
+        assert!(writable.exists("scenes/a.scene"));
+        assert_eq!(vfs.read("assets://scenes/a.scene").unwrap(), b"scene");
+        let read_only = Vfs::new();
+        read_only.mount("assets", Arc::new(ReadOnlySource), priority::BASE);
+        assert_eq!(read_only.write("assets://a.txt", b""), Err(VfsError::ReadOnly("assets://a.txt".to_owned())));
+    }
+    #[test]
+    fn directory_writes_replace_files_and_rotate_backups(){
+        let root = std::env::temp_dir().join(format!("omage-vfs-{}-backups", std::process::id()));
+        let source = DirectorySource::new(root.clone()).with_backups(2);
+        for version in ["1", "2", "3", "4"]{source.write("saves/save.toml", version.as_bytes()).unwrap()}
+        assert_eq!(source.read("saves/save.toml").unwrap().as_ref(), b"4");
+        let file = |name : &str| std::fs::read(root.join("saves").join(name)).ok();
+        assert_eq!(file("save.toml.1.bak"), Some(b"3".to_vec()));
+        assert_eq!(file("save.toml.2.bak"), Some(b"2".to_vec()));
+        assert_eq!(file("save.toml.3.bak"), None);
+        //Only the file and its backups are left, no temporary files.
+        assert_eq!(std::fs::read_dir(root.join("saves")).unwrap().count(), 3);
+        assert_eq!(DirectorySource::read_only(root.clone()).write("saves/save.toml", b""), Err(VfsError::ReadOnly("saves/save.toml".to_owned())));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+    #[test]
+    fn concurrent_directory_writes_do_not_clobber_each_other(){
+        let root = std::env::temp_dir().join(format!("omage-vfs-{}-concurrent", std::process::id()));
+        let source = Arc::new(DirectorySource::new(root.clone()));
+        let threads : Vec<_> = (0..8).map(|thread| {
+            let source = source.clone();
+            std::thread::spawn(move || {
+                for _ in 0..20{source.write("config.toml", format!("thread = {}", thread).repeat(1000).as_bytes()).unwrap()}
+            })
+        }).collect();
+        for thread in threads{thread.join().unwrap()}
+        let content = String::from_utf8(source.read("config.toml").unwrap().into_owned()).unwrap();
+        //One of the writes won as a whole.
+        let line = &content[.."thread = 0".len()];
+        assert!(line.starts_with("thread = "));
+        assert_eq!(content, line.repeat(1000));
+        assert_eq!(std::fs::read_dir(&root).unwrap().count(), 1);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+    #[test]
+    fn paths_cannot_leave_their_mount(){
+        let vfs = Vfs::new();
+        vfs.mount("assets", memory(&[("a.txt", "a")]), priority::BASE);
+        for path in ["assets://../a.txt", "assets://scenes/../../a.txt", "assets:///a.txt", "assets://./a.txt", "a.txt"]{
+            assert_eq!(vfs.read(path), Err(VfsError::InvalidPath(path.to_owned())), "{}", path);
+            assert_eq!(vfs.write(path, b""), Err(VfsError::InvalidPath(path.to_owned())), "{}", path);
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+use serde_derive::{Deserialize, Serialize};
+use slog::{info, warn, Logger};
+use omage_util::{FileType, PathManager};
+use omage_util::config::Versioned;
+use crate::input::{InputState, MouseButton, VirtualKeyCode};
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "device", content = "button")]
+pub enum Button{
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+}
+impl Button{
+    fn is_pressed(&self, input : &InputState) -> bool{
+        return match self{
+            Button::Key(key) => {input.is_pressed(*key)}
+            Button::Mouse(button) => {input.is_mouse_pressed(*button)}
+        }
+    }
+    fn just_pressed(&self, input : &InputState) -> bool{
+        return match self{
+            Button::Key(key) => {input.just_pressed(*key)}
+            Button::Mouse(button) => {input.mouse_just_pressed(*button)}
+        }
+    }
+    fn just_released(&self, input : &InputState) -> bool{
+        return match self{
+            Button::Key(key) => {input.just_released(*key)}
+            Button::Mouse(button) => {input.mouse_just_released(*button)}
+        }
+    }
+}
+#[derive(Copy, Clone, PartialEq, Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Modifiers{
+    pub shift : bool,
+    pub ctrl : bool,
+    pub alt : bool,
+    pub logo : bool,
+}
+///A chord of buttons that all have to be held together with the modifiers.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Binding{
+    #[serde(default)]
+    pub modifiers : Modifiers,
+    pub buttons : Vec<Button>,
+}
+impl Binding{
+    pub fn key(key : VirtualKeyCode) -> Self{
+        return Self{modifiers : Modifiers::default(), buttons : vec![Button::Key(key)]};
+    }
+    pub fn mouse(button : MouseButton) -> Self{
+        return Self{modifiers : Modifiers::default(), buttons : vec![Button::Mouse(button)]};
+    }
+    fn modifiers_held(&self, input : &InputState) -> bool{
+        let modifiers = input.modifiers();
+        return (!self.modifiers.shift || modifiers.shift()) && (!self.modifiers.ctrl || modifiers.ctrl()) &&
+            (!self.modifiers.alt || modifiers.alt()) && (!self.modifiers.logo || modifiers.logo());
+    }
+    fn is_pressed(&self, input : &InputState) -> bool{
+        return !self.buttons.is_empty() && self.modifiers_held(input) && self.buttons.iter().all(|button| button.is_pressed(input));
+    }
+    fn just_pressed(&self, input : &InputState) -> bool{
+        return self.is_pressed(input) && self.buttons.iter().any(|button| button.just_pressed(input));
+    }
+    fn just_released(&self, input : &InputState) -> bool{
+        return !self.buttons.is_empty() && self.buttons.iter().any(|button| button.just_released(input)) &&
+            self.buttons.iter().all(|button| button.is_pressed(input) || button.just_released(input));
+    }
+}
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum AxisSource{
+    MouseX,
+    MouseY,
+    ScrollX,
+    ScrollY,
+}
+///An axis driven by a positive and a negative set of bindings and optionally an analog source.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AxisBinding{
+    pub source : Option<AxisSource>,
+    pub scale : f64,
+    pub dead_zone : f64,
+    pub positive : Vec<Binding>,
+    pub negative : Vec<Binding>,
+}
+impl Default for AxisBinding{
+    fn default() -> Self {
+        return Self{
+            source : None,
+            scale : 1.0,
+            dead_zone : 0.0,
+            positive : vec![],
+            negative : vec![],
+        }
+    }
+}
+impl AxisBinding{
+    fn value(&self, input : &InputState) -> f64{
+        let mut value = 0.0;
+        if self.positive.iter().any(|binding| binding.is_pressed(input)){value += 1.0}
+        if self.negative.iter().any(|binding| binding.is_pressed(input)){value -= 1.0}
+        if let Some(source) = self.source{
+            value += match source{
+                AxisSource::MouseX => {input.mouse_delta().0}
+                AxisSource::MouseY => {input.mouse_delta().1}
+                AxisSource::ScrollX => {input.scroll_delta().0}
+                AxisSource::ScrollY => {input.scroll_delta().1}
+            };
+        }
+        return if value.abs() <= self.dead_zone{0.0}else{value * self.scale};
+    }
+}
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputConfig{
+    pub actions : BTreeMap<String, Vec<Binding>>,
+    pub axes : BTreeMap<String, AxisBinding>,
+}
+impl Versioned for InputConfig{}
+///Named actions and axes bound to inputs, stored in input.toml so they can be remapped without a rebuild.
+pub struct ActionMap{
+    logger : Logger,
+    path_manager : PathManager,
+    config : InputConfig,
+}
+impl ActionMap{
+    pub fn new(logger : Logger, path_manager : PathManager) -> Self{
+        let config = path_manager.load_file_or_default(&logger, "input", FileType::Config);
+        return Self{
+            logger,path_manager,config,
+        }
+    }
+    ///Adds the default bindings of an action unless the user already bound it.
+    pub fn register_action(&mut self, name : &str, bindings : Vec<Binding>){
+        if !self.config.actions.contains_key(name){
+            self.config.actions.insert(name.to_string(), bindings);
+            self.save();
+        }
+    }
+    ///Adds the default binding of an axis unless the user already bound it.
+    pub fn register_axis(&mut self, name : &str, binding : AxisBinding){
+        if !self.config.axes.contains_key(name){
+            self.config.axes.insert(name.to_string(), binding);
+            self.save();
+        }
+    }
+    pub fn rebind_action(&mut self, name : &str, bindings : Vec<Binding>){
+        info!(self.logger, "Rebinding action {}.", name);
+        self.config.actions.insert(name.to_string(), bindings);
+        self.save();
+    }
+    pub fn rebind_axis(&mut self, name : &str, binding : AxisBinding){
+        info!(self.logger, "Rebinding axis {}.", name);
+        self.config.axes.insert(name.to_string(), binding);
+        self.save();
+    }
+    pub fn config(&self) -> &InputConfig{
+        return &self.config;
+    }
+    pub fn pressed(&self, input : &InputState, action : &str) -> bool{
+        return self.config.actions.get(action).is_some_and(|bindings| bindings.iter().any(|binding| binding.is_pressed(input)));
+    }
+    pub fn just_pressed(&self, input : &InputState, action : &str) -> bool{
+        return self.config.actions.get(action).is_some_and(|bindings| bindings.iter().any(|binding| binding.just_pressed(input)));
+    }
+    pub fn just_released(&self, input : &InputState, action : &str) -> bool{
+        return self.config.actions.get(action).is_some_and(|bindings| bindings.iter().any(|binding| binding.just_released(input)));
+    }
+    pub fn axis(&self, input : &InputState, axis : &str) -> f64{
+        return self.config.axes.get(axis).map_or(0.0, |binding| binding.value(input));
+    }
+    fn save(&self){
+        if let Err(error) = self.path_manager.save_file("input", FileType::Config, &self.config){warn!(self.logger, "Failed to save the input bindings, {}.", error)}
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use std::path::PathBuf;
+    use slog::o;
+    use winit::event::{ElementState, ModifiersState, WindowEvent};
+    use omage_util::PathOptions;
+    use crate::input::tests::{button, key};
+    use super::*;
+
+    ///An action map saving to a config directory of its own in the temporary directory.
+    fn actions(name : &str) -> (ActionMap, PathBuf){
+        let directory = std::env::temp_dir().join(format!("omage-actions-{}-{}", std::process::id(), name));
+        let options = PathOptions{portable : true, config_directory : Some(directory.clone()), cache_directory : Some(directory.clone()), data_directory : Some(directory.clone()), assets_directory : Some(directory.clone())};
+        let actions = ActionMap::new(Logger::root(slog::Discard, o!()), PathManager::with_options("omage-test", options));
+        return (actions, directory);
+    }
+
+    #[test]
+    fn chords_need_every_button_and_modifier(){
+        let (mut actions, directory) = actions("chords");
+        let mut binding = Binding{modifiers : Modifiers{ctrl : true, ..Default::default()}, buttons : vec![Button::Key(VirtualKeyCode::S), Button::Mouse(MouseButton::Left)]};
+        actions.register_action("save", vec![binding.clone()]);
+        let mut input = InputState::default();
+        key(&mut input, VirtualKeyCode::S, ElementState::Pressed);
+        button(&mut input, MouseButton::Left, ElementState::Pressed);
+        assert!(!actions.pressed(&input, "save"));
+        input.handle_window_event(&WindowEvent::ModifiersChanged(ModifiersState::CTRL));
+        assert!(actions.pressed(&input, "save") && actions.just_pressed(&input, "save"));
+        input.end_frame();
+        assert!(actions.pressed(&input, "save") && !actions.just_pressed(&input, "save"));
+        button(&mut input, MouseButton::Left, ElementState::Released);
+        assert!(!actions.pressed(&input, "save") && actions.just_released(&input, "save"));
+        //Registering again keeps the bindings the user chose.
+        binding.modifiers.ctrl = false;
+        actions.rebind_action("save", vec![binding.clone()]);
+        actions.register_action("save", vec![Binding::key(VirtualKeyCode::F5)]);
+        assert_eq!(actions.config().actions["save"], vec![binding]);
+        assert!(!actions.pressed(&input, "missing"));
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+    #[test]
+    fn empty_bindings_are_never_pressed(){
+        let (mut actions, directory) = actions("empty");
+        actions.register_action("nothing", vec![Binding{modifiers : Modifiers::default(), buttons : vec![]}]);
+        let input = InputState::default();
+        assert!(!actions.pressed(&input, "nothing") && !actions.just_released(&input, "nothing"));
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+    #[test]
+    fn axes_combine_buttons_and_sources(){
+        let (mut actions, directory) = actions("axes");
+        actions.register_axis("move", AxisBinding{positive : vec![Binding::key(VirtualKeyCode::D)], negative : vec![Binding::key(VirtualKeyCode::A)], ..Default::default()});
+        actions.register_axis("look", AxisBinding{source : Some(AxisSource::MouseX), scale : 0.5, dead_zone : 1.0, ..Default::default()});
+        let mut input = InputState::default();
+        key(&mut input, VirtualKeyCode::D, ElementState::Pressed);
+        assert_eq!(actions.axis(&input, "move"), 1.0);
+        key(&mut input, VirtualKeyCode::A, ElementState::Pressed);
+        assert_eq!(actions.axis(&input, "move"), 0.0);
+        input.handle_device_event(&winit::event::DeviceEvent::MouseMotion{delta : (0.5, 0.0)});
+        assert_eq!(actions.axis(&input, "look"), 0.0);
+        input.handle_device_event(&winit::event::DeviceEvent::MouseMotion{delta : (3.5, 0.0)});
+        assert_eq!(actions.axis(&input, "look"), 2.0);
+        assert_eq!(actions.axis(&input, "missing"), 0.0);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,80 @@
+use winit::window::Window;
+use omage_renderer::Renderer;
+use crate::action::ActionMap;
+use crate::asset::AssetServer;
+use crate::input::InputState;
+use crate::error::SceneError;
+use crate::scene::{Entity, World};
+use crate::scene::file::SceneLoader;
+use crate::scene::system::Schedule;
+
+///User code driven by the engine thread, `fixed_update` runs at the configured tick rate and `update` once per frame.
+pub trait Application : Send{
+    fn init(&mut self, _context : &mut Context){}
+    ///Advances the simulation by exactly one tick of `dt` seconds.
+    fn fixed_update(&mut self, _context : &mut Context, _dt : f64){}
+    ///Called once per frame with the real frame time and how far the frame lies between the last and the next tick.
+    fn update(&mut self, _context : &mut Context, _dt : f64, _alpha : f64){}
+    fn shutdown(&mut self, _context : &mut Context){}
+}
+
+///What the application can reach from its callbacks.
+pub struct Context<'a>{
+    pub(crate) input : &'a InputState,
+    pub(crate) actions : &'a mut ActionMap,
+    pub(crate) window : Option<&'a Window>,
+    pub(crate) renderer : Option<&'a Renderer>,
+    pub(crate) world : &'a mut World,
+    pub(crate) schedule : &'a mut Schedule,
+    pub(crate) scenes : &'a mut SceneLoader,
+    pub(crate) assets : &'a AssetServer,
+    pub(crate) exit : bool,
+}
+impl<'a> Context<'a>{
+    pub fn input(&self) -> &InputState{
+        return self.input;
+    }
+    pub fn actions(&self) -> &ActionMap{
+        return self.actions;
+    }
+    pub fn actions_mut(&mut self) -> &mut ActionMap{
+        return self.actions;
+    }
+    pub fn world(&self) -> &World{
+        return self.world;
+    }
+    pub fn world_mut(&mut self) -> &mut World{
+        return self.world;
+    }
+    ///The systems run on the world after every fixed update.
+    pub fn schedule_mut(&mut self) -> &mut Schedule{
+        return self.schedule;
+    }
+    ///The component types stored in scene files.
+    pub fn scenes_mut(&mut self) -> &mut SceneLoader{
+        return self.scenes;
+    }
+    ///Loads assets in the background, the server can be cloned to load from other threads.
+    pub fn assets(&self) -> &AssetServer{
+        return self.assets;
+    }
+    ///Spawns the entities of assets/scenes/<name>.toml into the world.
+    pub fn load_scene(&mut self, name : &str) -> Result<Vec<Entity>, SceneError>{
+        return self.scenes.load(self.world, self.assets, name);
+    }
+    pub fn save_scene(&self, name : &str) -> Result<(), SceneError>{
+        return self.scenes.save(self.world, name);
+    }
+    ///The window, `None` in headless mode.
+    pub fn window(&self) -> Option<&Window>{
+        return self.window;
+    }
+    ///The renderer, for compute work and the enabled device capabilities, `None` in headless mode.
+    pub fn renderer(&self) -> Option<&Renderer>{
+        return self.renderer;
+    }
+    ///Stops the event loop once the current callback returns.
+    pub fn exit(&mut self){
+        self.exit = true;
+    }
+}
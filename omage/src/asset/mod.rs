@@ -0,0 +1,254 @@
+pub mod types;
+
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Formatter};
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::sync::atomic::{AtomicU64, Ordering};
+use crossbeam_channel::{Receiver, Sender};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use slog::{o, warn, Logger};
+use omage_renderer::objects::upload::GpuAsset;
+use omage_util::archive::relative_path;
+use omage_util::vfs::Vfs;
+use crate::error::AssetError;
+
+///A type loaded from a file under the assets directory.
+pub trait Asset : Send + Sync + Sized + 'static{
+    fn load(bytes : &[u8]) -> Result<Self, String>;
+    ///The data the renderer needs for this asset, `None` for assets that stay on the CPU.
+    fn gpu(&self) -> Option<GpuAsset>{
+        return None;
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum LoadState{
+    Loading,
+    Loaded,
+    Failed(AssetError),
+    ///Every handle was dropped and the asset was freed.
+    Unloaded,
+}
+
+///Work for the render thread, drained by the engine every frame.
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) enum GpuTask{
+    Upload(u64, Arc<GpuAsset>),
+    Release(u64),
+}
+
+///Sends the id of an asset to the server once its last handle is dropped.
+struct HandleRef{
+    id : u64,
+    dropped : Sender<u64>,
+}
+impl Drop for HandleRef{
+    fn drop(&mut self) {
+        let _ = self.dropped.send(self.id);
+    }
+}
+
+///A reference counted reference to an asset, the asset is unloaded once every handle to it was dropped.
+pub struct Handle<T : Asset>{
+    inner : Arc<HandleRef>,
+    _marker : PhantomData<fn() -> T>,
+}
+impl<T : Asset> Handle<T>{
+    ///The id the asset is known under, also by the renderer.
+    pub fn id(&self) -> u64{
+        return self.inner.id;
+    }
+}
+impl<T : Asset> Clone for Handle<T>{
+    fn clone(&self) -> Self {
+        return Self{inner : self.inner.clone(), _marker : PhantomData};
+    }
+}
+impl<T : Asset> PartialEq for Handle<T>{
+    fn eq(&self, other : &Self) -> bool {
+        return self.id() == other.id();
+    }
+}
+impl<T : Asset> Debug for Handle<T>{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "Handle<{}>({})", std::any::type_name::<T>(), self.id());
+    }
+}
+
+struct Entry{
+    path : String,
+    type_id : TypeId,
+    state : LoadState,
+    value : Option<Arc<dyn Any + Send + Sync>>,
+    uploaded : bool,
+    ///Loads the file again as the type of the entry.
+    load : fn(Arc<Shared>, u64, String),
+}
+
+struct Shared{
+    logger : Logger,
+    vfs : Vfs,
+    next_id : AtomicU64,
+    entries : RwLock<HashMap<u64, Entry>>,
+    ///The live handle of every loaded path and type, so loading a path twice shares the asset.
+    paths : Mutex<HashMap<(TypeId, String), Weak<HandleRef>>>,
+    dropped_sender : Sender<u64>,
+    dropped : Receiver<u64>,
+    gpu_tasks : Mutex<Vec<GpuTask>>,
+    ///Files under the root that changed on disk since the last update.
+    changed : Mutex<HashSet<String>>,
+    reloaded : Mutex<Vec<String>>,
+    watcher : Mutex<Option<RecommendedWatcher>>,
+}
+
+///Loads assets from `assets://` on the rayon pool, cheap to clone and share between threads.
+#[derive(Clone)]
+pub struct AssetServer{
+    shared : Arc<Shared>,
+}
+impl AssetServer{
+    pub fn new(logger : Logger, vfs : Vfs) -> Self{
+        let (dropped_sender, dropped) = crossbeam_channel::unbounded();
+        return Self{shared : Arc::new(Shared{
+            logger,vfs,dropped_sender,dropped,
+            next_id : AtomicU64::new(1),
+            entries : RwLock::new(HashMap::new()),
+            paths : Mutex::new(HashMap::new()),
+            gpu_tasks : Mutex::new(vec![]),
+            changed : Mutex::new(HashSet::new()),
+            reloaded : Mutex::new(vec![]),
+            watcher : Mutex::new(None),
+        })}
+    }
+    ///Starts loading the file at the path below `assets://`, the handle is returned right away.
+    pub fn load<T : Asset>(&self, path : &str) -> Handle<T>{
+        let mut paths = self.shared.paths.lock().unwrap();
+        let key = (TypeId::of::<T>(), path.to_owned());
+        if let Some(inner) = paths.get(&key).and_then(Weak::upgrade){
+            return Handle{inner, _marker : PhantomData};
+        }
+        let id = self.shared.next_id.fetch_add(1, Ordering::Relaxed);
+        let inner = Arc::new(HandleRef{id, dropped : self.shared.dropped_sender.clone()});
+        paths.insert(key, Arc::downgrade(&inner));
+        self.shared.entries.write().unwrap().insert(id, Entry{
+            path : path.to_owned(),
+            type_id : TypeId::of::<T>(),
+            state : LoadState::Loading,
+            value : None,
+            uploaded : false,
+            load : Shared::spawn_load::<T>,
+        });
+        Shared::spawn_load::<T>(self.shared.clone(), id, path.to_owned());
+        return Handle{inner, _marker : PhantomData};
+    }
+    pub fn state<T : Asset>(&self, handle : &Handle<T>) -> LoadState{
+        return self.shared.entries.read().unwrap().get(&handle.id()).map(|entry| entry.state.clone()).unwrap_or(LoadState::Unloaded);
+    }
+    ///The asset, once it finished loading.
+    pub fn get<T : Asset>(&self, handle : &Handle<T>) -> Option<Arc<T>>{
+        let value = self.shared.entries.read().unwrap().get(&handle.id())?.value.clone()?;
+        return value.downcast().ok();
+    }
+    ///The path the asset was loaded from, below `assets://`.
+    pub fn path<T : Asset>(&self, handle : &Handle<T>) -> Option<String>{
+        return self.shared.entries.read().unwrap().get(&handle.id()).map(|entry| entry.path.clone());
+    }
+    ///Whether any mounted source has a file at the path.
+    pub fn exists(&self, path : &str) -> bool{
+        return self.shared.vfs.exists(&format!("assets://{}", path));
+    }
+    ///Watches the directories mounted under `assets://`, changed files are reloaded by the next updates.
+    pub fn watch(&self) -> Result<(), String>{
+        let roots = self.shared.vfs.directories("assets");
+        if roots.is_empty(){return Err("no directory is mounted under assets://".to_owned())}
+        let watched_roots = roots.clone();
+        let shared = Arc::downgrade(&self.shared);
+        let mut watcher = notify::recommended_watcher(move |event : notify::Result<Event>| {
+            let (shared, event) = match (shared.upgrade(), event){
+                (Some(shared), Ok(event)) => {(shared, event)}
+                _ => {return}
+            };
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)){return}
+            let mut changed = shared.changed.lock().unwrap();
+            for path in event.paths.iter().filter(|path| path.is_file()){
+                if let Some(relative) = watched_roots.iter().find_map(|root| path.strip_prefix(root).ok()){changed.insert(relative_path(relative));}
+            }
+        }).map_err(|error| error.to_string())?;
+        //Directories that do not exist yet, like an empty mod directory, are skipped.
+        for root in roots.iter().filter(|root| root.is_dir()){
+            watcher.watch(root, RecursiveMode::Recursive).map_err(|error| error.to_string())?;
+        }
+        *self.shared.watcher.lock().unwrap() = Some(watcher);
+        return Ok(());
+    }
+    ///Unloads the assets whose handles were all dropped and starts reloading the changed ones.
+    pub fn update(&self){
+        let mut paths = self.shared.paths.lock().unwrap();
+        let mut entries = self.shared.entries.write().unwrap();
+        for path in std::mem::take(&mut *self.shared.changed.lock().unwrap()){
+            let mut loaded = false;
+            for (id, entry) in entries.iter().filter(|(_, entry)| entry.path == path){
+                (entry.load)(self.shared.clone(), *id, path.clone());
+                loaded = true;
+            }
+            //Files no asset was loaded from, like scenes, are announced right away.
+            if !loaded{self.shared.reloaded.lock().unwrap().push(path)}
+        }
+        while let Ok(id) = self.shared.dropped.try_recv(){
+            if let Some(entry) = entries.remove(&id){
+                let key = (entry.type_id, entry.path);
+                //The path may already belong to a newer load of the same file.
+                if paths.get(&key).is_some_and(|handle| handle.strong_count() == 0){paths.remove(&key);}
+                if entry.uploaded{self.shared.gpu_tasks.lock().unwrap().push(GpuTask::Release(id))}
+            }
+        }
+    }
+    pub(crate) fn take_gpu_tasks(&self) -> Vec<GpuTask>{
+        return std::mem::take(&mut *self.shared.gpu_tasks.lock().unwrap());
+    }
+    ///The paths of the files that changed on disk and whose assets were swapped for the new version.
+    pub(crate) fn take_reloaded(&self) -> Vec<String>{
+        return std::mem::take(&mut *self.shared.reloaded.lock().unwrap());
+    }
+}
+impl Shared{
+    fn spawn_load<T : Asset>(self : Arc<Self>, id : u64, path : String){
+        rayon::spawn(move || {
+            let result = match self.vfs.read_with(&format!("assets://{}", path), T::load){
+                Ok(result) => {result.map_err(|error| AssetError::Decode(path.clone(), error))}
+                Err(error) => {Err(AssetError::Io(path.clone(), error.to_string()))}
+            };
+            self.finish::<T>(id, &path, result);
+        });
+    }
+    ///Stores the result of a load, unless the asset was unloaded in the meantime.
+    ///A reload replaces the asset in one step, the renderer swaps the GPU side under the same id.
+    fn finish<T : Asset>(&self, id : u64, path : &str, result : Result<T, AssetError>){
+        let mut entries = self.entries.write().unwrap();
+        let entry = match entries.get_mut(&id){
+            Some(entry) => {entry}
+            None => {return}
+        };
+        let reload = entry.value.is_some();
+        match result{
+            Ok(asset) => {
+                if let Some(gpu) = asset.gpu(){
+                    self.gpu_tasks.lock().unwrap().push(GpuTask::Upload(id, Arc::new(gpu)));
+                    entry.uploaded = true;
+                }
+                entry.value = Some(Arc::new(asset));
+                entry.state = LoadState::Loaded;
+                if reload{self.reloaded.lock().unwrap().push(path.to_owned())}
+            }
+            Err(error) if reload => {
+                warn!(self.logger.new(o!("thread" => rayon::current_thread_index())), "Failed to reload {}, keeping the previous version, {}.", path, error);
+            }
+            Err(error) => {
+                warn!(self.logger.new(o!("thread" => rayon::current_thread_index())), "Failed to load {}, {}.", path, error);
+                entry.state = LoadState::Failed(error);
+            }
+        }
+    }
+}
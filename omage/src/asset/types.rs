@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use png::{ColorType, Transformations};
+use serde::de::DeserializeOwned;
+use omage_renderer::objects::upload::{GpuAsset, Vertex};
+use crate::asset::Asset;
+
+///An RGBA8 image decoded from a PNG file.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Texture{
+    pub width : u32,
+    pub height : u32,
+    pub pixels : Vec<u8>,
+}
+impl Asset for Texture{
+    fn load(bytes : &[u8]) -> Result<Self, String>{
+        let mut decoder = png::Decoder::new(bytes);
+        decoder.set_transformations(Transformations::EXPAND | Transformations::STRIP_16);
+        let mut reader = decoder.read_info().map_err(|error| error.to_string())?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).map_err(|error| error.to_string())?;
+        buffer.truncate(info.buffer_size());
+        let pixels = match info.color_type{
+            ColorType::Rgba => {buffer}
+            ColorType::Rgb => {buffer.chunks_exact(3).flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255]).collect()}
+            ColorType::GrayscaleAlpha => {buffer.chunks_exact(2).flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]]).collect()}
+            ColorType::Grayscale => {buffer.iter().flat_map(|value| [*value, *value, *value, 255]).collect()}
+            ColorType::Indexed => {return Err("indexed colors were not expanded".to_owned())}
+        };
+        return Ok(Self{width : info.width, height : info.height, pixels});
+    }
+    fn gpu(&self) -> Option<GpuAsset>{
+        return Some(GpuAsset::Texture{width : self.width, height : self.height, pixels : self.pixels.clone()});
+    }
+}
+
+///A triangle mesh read from a Wavefront OBJ file, polygons are triangulated as fans.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct MeshData{
+    pub vertices : Vec<Vertex>,
+    pub indices : Vec<u32>,
+}
+impl Asset for MeshData{
+    fn load(bytes : &[u8]) -> Result<Self, String>{
+        let text = std::str::from_utf8(bytes).map_err(|error| error.to_string())?;
+        let (mut positions, mut normals, mut uvs) = (vec![], vec![], vec![]);
+        let mut mesh = MeshData::default();
+        let mut indices : HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+        for (number, line) in text.lines().enumerate(){
+            let mut words = line.split_whitespace();
+            let error = |message : &str| format!("line {}, {}", number + 1, message);
+            let floats = |words : std::str::SplitWhitespace| words.map(|word| word.parse::<f32>()).collect::<Result<Vec<f32>, _>>().map_err(|parse| error(&parse.to_string()));
+            match words.next(){
+                Some("v") => {
+                    let values = floats(words)?;
+                    if values.len() < 3{return Err(error("a position needs three coordinates"))}
+                    positions.push([values[0], values[1], values[2]]);
+                }
+                Some("vn") => {
+                    let values = floats(words)?;
+                    if values.len() < 3{return Err(error("a normal needs three coordinates"))}
+                    normals.push([values[0], values[1], values[2]]);
+                }
+                Some("vt") => {
+                    let values = floats(words)?;
+                    if values.is_empty(){return Err(error("a texture coordinate needs at least one value"))}
+                    uvs.push([values[0], values.get(1).copied().unwrap_or(0.0)]);
+                }
+                Some("f") => {
+                    let mut face = vec![];
+                    for word in words{
+                        let mut parts = word.split('/');
+                        let position = parts.next().ok_or_else(|| error("empty face vertex"))?;
+                        let position = resolve_index(position, positions.len()).ok_or_else(|| error("invalid position index"))?;
+                        let uv = match parts.next().filter(|part| !part.is_empty()){
+                            Some(uv) => {Some(resolve_index(uv, uvs.len()).ok_or_else(|| error("invalid texture coordinate index"))?)}
+                            None => {None}
+                        };
+                        let normal = match parts.next().filter(|part| !part.is_empty()){
+                            Some(normal) => {Some(resolve_index(normal, normals.len()).ok_or_else(|| error("invalid normal index"))?)}
+                            None => {None}
+                        };
+                        let index = *indices.entry((position, uv, normal)).or_insert_with(|| {
+                            mesh.vertices.push(Vertex{
+                                position : positions[position],
+                                normal : normal.map(|normal| normals[normal]).unwrap_or_default(),
+                                uv : uv.map(|uv| uvs[uv]).unwrap_or_default(),
+                            });
+                            mesh.vertices.len() as u32 - 1
+                        });
+                        face.push(index);
+                    }
+                    if face.len() < 3{return Err(error("a face needs at least three vertices"))}
+                    for corner in 1..face.len() - 1{
+                        mesh.indices.extend_from_slice(&[face[0], face[corner], face[corner + 1]]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        return Ok(mesh);
+    }
+    fn gpu(&self) -> Option<GpuAsset>{
+        return Some(GpuAsset::Mesh{vertices : self.vertices.clone(), indices : self.indices.clone()});
+    }
+}
+///Turns a one based OBJ index, negative ones counting from the end, into a zero based one.
+fn resolve_index(word : &str, count : usize) -> Option<usize>{
+    let index = word.parse::<i64>().ok()?;
+    let index = if index < 0 {count as i64 + index} else {index - 1};
+    return (0..count as i64).contains(&index).then_some(index as usize);
+}
+
+pub struct Font(pub fontdue::Font);
+impl Asset for Font{
+    fn load(bytes : &[u8]) -> Result<Self, String>{
+        return fontdue::Font::from_bytes(bytes, fontdue::FontSettings::default()).map(Font).map_err(str::to_owned);
+    }
+}
+
+///A TOML file deserialized into `T`, for configuration shipped with the assets.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Toml<T>(pub T);
+impl<T : DeserializeOwned + Send + Sync + 'static> Asset for Toml<T>{
+    fn load(bytes : &[u8]) -> Result<Self, String>{
+        return toml::from_slice(bytes).map(Toml).map_err(|error| error.to_string());
+    }
+}
+
+///A compiled SPIR-V module.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Shader{
+    pub words : Vec<u32>,
+}
+impl Asset for Shader{
+    fn load(bytes : &[u8]) -> Result<Self, String>{
+        if !bytes.len().is_multiple_of(4){return Err("the length is not a multiple of four".to_owned())}
+        let words : Vec<u32> = bytes.chunks_exact(4).map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]])).collect();
+        if words.first() != Some(&0x07230203){return Err("missing the SPIR-V magic number".to_owned())}
+        return Ok(Self{words});
+    }
+}
+
+///The samples of a WAV file, interleaved by channel as stored in the file.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Sound{
+    pub channels : u16,
+    pub sample_rate : u32,
+    pub bits_per_sample : u16,
+    ///Whether the samples are IEEE floats instead of integers.
+    pub float : bool,
+    pub samples : Vec<u8>,
+}
+impl Asset for Sound{
+    fn load(bytes : &[u8]) -> Result<Self, String>{
+        if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE"{return Err("not a RIFF WAVE file".to_owned())}
+        let u16_at = |offset : usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+        let u32_at = |offset : usize| u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]);
+        let mut format = None;
+        let mut offset = 12;
+        while offset + 8 <= bytes.len(){
+            let size = u32_at(offset + 4) as usize;
+            let start = offset + 8;
+            let end = start.checked_add(size).filter(|end| *end <= bytes.len()).ok_or_else(|| "truncated chunk".to_owned())?;
+            match &bytes[offset..offset + 4]{
+                b"fmt " => {
+                    if size < 16{return Err("the format chunk is too short".to_owned())}
+                    format = Some((u16_at(start), u16_at(start + 2), u32_at(start + 4), u16_at(start + 14)));
+                }
+                b"data" => {
+                    let (tag, channels, sample_rate, bits_per_sample) = format.ok_or_else(|| "the data chunk comes before the format chunk".to_owned())?;
+                    if tag != 1 && tag != 3{return Err(format!("unsupported format {}, only PCM and float samples are", tag))}
+                    return Ok(Self{channels, sample_rate, bits_per_sample, float : tag == 3, samples : bytes[start..end].to_vec()});
+                }
+                _ => {}
+            }
+            //Chunks are padded to an even size.
+            offset = end + size % 2;
+        }
+        return Err("missing the data chunk".to_owned());
+    }
+}
@@ -0,0 +1,203 @@
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use omage_renderer::capabilities::DeviceCapabilities;
+use crate::EngineTask;
+use crate::error::EngineError;
+use crate::event::{EngineEvent, Subscribers};
+use crate::input::InputState;
+use crate::window::{FullscreenMode, WindowConfig};
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum EngineCommand{
+    SetWindowName(String),
+    SetWindowSize(u32, u32),
+    SetWindowPosition(i32, i32),
+    SetFullscreen(FullscreenMode, Option<usize>),
+    SetResizable(bool),
+    SetDecorations(bool),
+    SetAlwaysOnTop(bool),
+    SetCursorGrab(bool),
+    WindowConfig,
+    SetCloseVeto(bool),
+    Exit,
+}
+#[derive(Clone, PartialEq, Debug)]
+pub enum Response{
+    Done,
+    WindowConfig(WindowConfig),
+}
+pub type Callback = Box<dyn FnOnce(Result<Response, EngineError>) + Send>;
+pub enum ReplyTo{
+    Channel(Sender<(u64, Result<Response, EngineError>)>),
+    ///Runs on the engine thread once the command was handled.
+    Callback(Callback),
+}
+pub struct Request{
+    pub id : u64,
+    pub command : EngineCommand,
+    pub reply : ReplyTo,
+}
+impl Request{
+    pub fn reply(self, result : Result<Response, EngineError>){
+        match self.reply{
+            ReplyTo::Channel(sender) => {let _ = sender.send((self.id, result));}
+            ReplyTo::Callback(callback) => {callback(result)}
+        }
+    }
+}
+
+///The state the engine thread publishes for the handles.
+#[derive(Clone, Default)]
+pub struct SharedState{
+    ///The input state of the last finished frame.
+    pub(crate) input : Arc<RwLock<InputState>>,
+    ///The capabilities of the current device, `None` in headless mode.
+    pub(crate) capabilities : Arc<RwLock<Option<DeviceCapabilities>>>,
+}
+
+///A cloneable sender of engine commands that stays usable from any thread while the event loop runs.
+///Blocking on a reply from inside the application callbacks deadlocks, since those run on the engine thread.
+#[derive(Clone)]
+pub struct EngineHandle{
+    sender : Sender<EngineTask>,
+    next_id : Arc<AtomicU64>,
+    subscribers : Subscribers,
+    shared : SharedState,
+}
+impl EngineHandle{
+    pub(crate) fn new(sender : Sender<EngineTask>, subscribers : Subscribers, shared : SharedState) -> Self{
+        return Self{sender, subscribers, shared, next_id : Arc::new(AtomicU64::new(0))};
+    }
+    ///A snapshot of the input state of the last finished frame, readable from any thread while `dispatch` blocks.
+    pub fn input(&self) -> InputState{
+        return self.shared.input.read().unwrap().clone();
+    }
+    ///The optional device features and extensions the renderer enabled, `None` in headless mode.
+    pub fn capabilities(&self) -> Option<DeviceCapabilities>{
+        return self.shared.capabilities.read().unwrap().clone();
+    }
+    ///Returns a channel receiving every engine event published from now on.
+    pub fn subscribe(&self) -> Receiver<EngineEvent>{
+        return self.subscribers.subscribe();
+    }
+    pub fn set_window_name(&self, name : &str) -> Call<()>{
+        return self.call(EngineCommand::SetWindowName(name.to_owned()), done);
+    }
+    ///Sets the inner size in physical pixels.
+    pub fn set_window_size(&self, width : u32, height : u32) -> Call<()>{
+        return self.call(EngineCommand::SetWindowSize(width, height), done);
+    }
+    ///Sets the outer position in physical pixels.
+    pub fn set_window_position(&self, x : i32, y : i32) -> Call<()>{
+        return self.call(EngineCommand::SetWindowPosition(x, y), done);
+    }
+    ///Switches the fullscreen mode, on the monitor with the given index or the primary monitor.
+    pub fn set_fullscreen(&self, mode : FullscreenMode, monitor : Option<usize>) -> Call<()>{
+        return self.call(EngineCommand::SetFullscreen(mode, monitor), done);
+    }
+    pub fn set_resizable(&self, resizable : bool) -> Call<()>{
+        return self.call(EngineCommand::SetResizable(resizable), done);
+    }
+    pub fn set_decorations(&self, decorations : bool) -> Call<()>{
+        return self.call(EngineCommand::SetDecorations(decorations), done);
+    }
+    pub fn set_always_on_top(&self, always_on_top : bool) -> Call<()>{
+        return self.call(EngineCommand::SetAlwaysOnTop(always_on_top), done);
+    }
+    pub fn set_cursor_grab(&self, grab : bool) -> Call<()>{
+        return self.call(EngineCommand::SetCursorGrab(grab), done);
+    }
+    ///The current window settings, including the live geometry.
+    pub fn window_config(&self) -> Call<WindowConfig>{
+        return self.call(EngineCommand::WindowConfig, |response| match response{
+            Response::WindowConfig(config) => {Some(config)}
+            _ => {None}
+        });
+    }
+    ///While set, closing the window only publishes `EngineEvent::CloseRequested` and leaves the exit to the subscriber.
+    pub fn set_close_veto(&self, veto : bool) -> Call<()>{
+        return self.call(EngineCommand::SetCloseVeto(veto), done);
+    }
+    ///Stops the event loop, the application's shutdown still runs.
+    pub fn exit(&self) -> Call<()>{
+        return self.call(EngineCommand::Exit, done);
+    }
+    fn call<T>(&self, command : EngineCommand, convert : fn(Response) -> Option<T>) -> Call<T>{
+        return Call{
+            handle : self.clone(),
+            id : self.next_id.fetch_add(1, Ordering::Relaxed),
+            command,convert,
+        }
+    }
+}
+fn done(response : Response) -> Option<()>{
+    return match response{
+        Response::Done => {Some(())}
+        _ => {None}
+    }
+}
+fn convert<T>(result : Result<Response, EngineError>, convert : fn(Response) -> Option<T>) -> Result<T, EngineError>{
+    return result.and_then(|response| convert(response).ok_or(EngineError::UnexpectedResponse));
+}
+
+///A command that was not sent yet, the way it is sent decides how the typed reply is delivered.
+#[must_use]
+pub struct Call<T>{
+    handle : EngineHandle,
+    id : u64,
+    command : EngineCommand,
+    convert : fn(Response) -> Option<T>,
+}
+impl<T : 'static> Call<T>{
+    pub fn id(&self) -> u64{
+        return self.id;
+    }
+    ///Sends the command without waiting, the reply is collected from the returned pending reply.
+    pub fn send(self) -> Pending<T>{
+        let (sender, receiver) = crossbeam_channel::bounded(1);
+        let request = Request{id : self.id, command : self.command, reply : ReplyTo::Channel(sender)};
+        let disconnected = self.handle.sender.send(EngineTask::Request(request)).is_err();
+        return Pending{id : self.id, receiver, convert : self.convert, disconnected};
+    }
+    ///Sends the command and blocks until the engine thread replied.
+    pub fn wait(self) -> Result<T, EngineError>{
+        return self.send().wait();
+    }
+    ///Sends the command and runs the callback on the engine thread with the reply.
+    pub fn then<F>(self, callback : F) -> Result<(), EngineError> where F : FnOnce(Result<T, EngineError>) + Send + 'static{
+        let converter = self.convert;
+        let callback : Callback = Box::new(move |result| callback(convert(result, converter)));
+        let request = Request{id : self.id, command : self.command, reply : ReplyTo::Callback(callback)};
+        return self.handle.sender.send(EngineTask::Request(request)).map_err(|_| EngineError::Disconnected);
+    }
+}
+
+///The reply of a sent command.
+pub struct Pending<T>{
+    id : u64,
+    receiver : Receiver<(u64, Result<Response, EngineError>)>,
+    convert : fn(Response) -> Option<T>,
+    disconnected : bool,
+}
+impl<T> Pending<T>{
+    pub fn id(&self) -> u64{
+        return self.id;
+    }
+    pub fn wait(self) -> Result<T, EngineError>{
+        if self.disconnected{return Err(EngineError::Disconnected)}
+        return match self.receiver.recv(){
+            Ok((_, result)) => {convert(result, self.convert)}
+            Err(_) => {Err(EngineError::Disconnected)}
+        }
+    }
+    ///Returns the reply if it arrived already.
+    pub fn try_wait(&self) -> Option<Result<T, EngineError>>{
+        if self.disconnected{return Some(Err(EngineError::Disconnected))}
+        return match self.receiver.try_recv(){
+            Ok((_, result)) => {Some(convert(result, self.convert))}
+            Err(TryRecvError::Empty) => {None}
+            Err(TryRecvError::Disconnected) => {Some(Err(EngineError::Disconnected))}
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use std::backtrace::Backtrace;
+use std::fmt::Write;
+use std::panic::PanicHookInfo;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use slog::{crit, o, Logger};
+use omage_renderer::instance::RenderConfig;
+use omage_util::logging::flush_logs;
+use omage_util::panic_message;
+
+///What the crash report knows about the running engine, filled in while the engine starts.
+struct CrashInfo{
+    logger : Logger,
+    directory : PathBuf,
+    mode : String,
+    render_config : Option<String>,
+    gpu : Option<String>,
+}
+
+static INFO : Mutex<Option<CrashInfo>> = Mutex::new(None);
+
+fn info() -> MutexGuard<'static, Option<CrashInfo>>{
+    return INFO.lock().unwrap_or_else(PoisonError::into_inner);
+}
+
+///Installs a panic hook writing a crash report into the directory, then logging the panic and flushing the loggers.
+///The previously installed hook runs afterwards, a later engine only replaces the logger and directory.
+pub(crate) fn install(logger : Logger, directory : PathBuf, mode : String){
+    let installed = info().replace(CrashInfo{logger, directory, mode, render_config : None, gpu : None}).is_some();
+    if installed{return}
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |panic| {
+        report(panic);
+        previous(panic);
+    }));
+}
+pub(crate) fn set_render_config(config : &RenderConfig){
+    if let Some(info) = info().as_mut(){info.render_config = toml::to_string(config).ok()}
+}
+pub(crate) fn set_gpu(name : &str){
+    if let Some(info) = info().as_mut(){info.gpu = Some(name.to_owned())}
+}
+
+fn report(panic : &PanicHookInfo){
+    let info = info();
+    let info = match info.as_ref(){
+        Some(info) => {info}
+        None => {return}
+    };
+    let message = panic_message(panic.payload());
+    let location = panic.location().map_or_else(|| "an unknown location".to_owned(), |location| location.to_string());
+    let thread = std::thread::current().name().unwrap_or("unnamed").to_owned();
+    let thread = match rayon::current_thread_index(){
+        Some(index) => {format!("{}, rayon worker {}", thread, index)}
+        None => {thread}
+    };
+    let now = chrono::Utc::now();
+    let mut report = String::new();
+    let _ = writeln!(report, "omage {} crashed at {}", env!("CARGO_PKG_VERSION"), now.to_rfc3339());
+    let _ = writeln!(report, "Panic: {}", message);
+    let _ = writeln!(report, "Location: {}", location);
+    let _ = writeln!(report, "Thread: {}", thread);
+    let _ = writeln!(report, "Mode: {}", info.mode);
+    let _ = writeln!(report, "Platform: {} {}", std::env::consts::OS, std::env::consts::ARCH);
+    let _ = writeln!(report, "GPU: {}", info.gpu.as_deref().unwrap_or("none"));
+    let _ = writeln!(report, "\nRender config:\n{}", info.render_config.as_deref().unwrap_or("none\n"));
+    let _ = writeln!(report, "Backtrace:\n{}", Backtrace::force_capture());
+    let path = info.directory.join(format!("crash-{}.txt", now.format("%Y-%m-%d-%H-%M-%S")));
+    let logger = info.logger.new(o!("thread" => rayon::current_thread_index()));
+    match std::fs::create_dir_all(&info.directory).and_then(|_| std::fs::write(&path, report)){
+        Ok(()) => {crit!(logger, "Panicked at {}, {}, wrote the crash report {:?}.", location, message, path)}
+        Err(error) => {crit!(logger, "Panicked at {}, {}, failed to write the crash report {:?}, {}.", location, message, path, error)}
+    }
+    flush_logs();
+}
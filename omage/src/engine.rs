@@ -1,5 +1,9 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use crossbeam_channel::{Receiver, Sender};
-use slog::{info, Logger};
+use serde_derive::{Deserialize, Serialize};
+use slog::{crit, info, o, warn, Logger};
 use winit::event::{Event, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
 use winit::platform::run_return::EventLoopExtRunReturn;
@@ -7,57 +11,417 @@ use winit::platform::run_return::EventLoopExtRunReturn;
 use winit::platform::unix::EventLoopExtUnix;
 #[cfg(target_os = "windows")]
 use winit::platform::windows::EventLoopExtWindows;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
 use winit::window::Window;
 use omage_renderer::instance::RenderInstance;
-use omage_renderer::Renderer;
+use omage_renderer::{Renderer, RenderResult};
 use omage_util::{FileType, PathManager};
-use crate::{EngineResult, EngineTask};
+use omage_util::archive::Archive;
+use omage_util::config::Versioned;
+use omage_util::vfs::{priority, DirectorySource};
+use crate::{crash, EngineMode, EngineOptions, EngineResult, EngineTask};
+use crate::action::ActionMap;
+use crate::asset::{AssetServer, GpuTask};
+use crate::application::{Application, Context};
+use crate::command::{EngineCommand, Request, Response, SharedState};
+use crate::error::EngineError;
+use crate::event::{EngineEvent, Subscribers};
+use crate::input::InputState;
+use crate::scene::World;
+use crate::scene::file::SceneLoader;
+use crate::scene::render::{extract, resolve_assets};
+use crate::scene::system::Schedule;
+use crate::scene::transform::{propagate_transforms, Children, GlobalTransform, Parent, Transform};
+use crate::window::WindowConfig;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EngineConfig{
+    ///Fixed updates per second.
+    pub tick_rate : f64,
+    ///The longest frame time in seconds fed into the accumulator, so a stall does not trigger an endless catch up.
+    pub max_frame_time : f64,
+    ///Reloads assets when their files change, on by default in debug builds, only loose assets are watched.
+    pub hot_reload : bool,
+    ///Mounts this directory above every other asset source, usually the assets directory of the source tree so edits apply without copying them.
+    pub asset_source : Option<PathBuf>,
+}
+impl Versioned for EngineConfig{}
+impl Default for EngineConfig{
+    fn default() -> Self {
+        return Self{
+            tick_rate : 60.0,
+            max_frame_time : 0.25,
+            hot_reload : cfg!(debug_assertions),
+            asset_source : None,
+        }
+    }
+}
+impl EngineConfig{
+    ///Replaces timings that would stall or spin the fixed timestep loop, like a zero or negative tick rate, with the defaults.
+    fn validate(&mut self, logger : &Logger){
+        let default = Self::default();
+        if !(self.tick_rate.is_finite() && self.tick_rate > 0.0){
+            warn!(logger, "Invalid tick rate {}, using {}.", self.tick_rate, default.tick_rate);
+            self.tick_rate = default.tick_rate;
+        }
+        if !(self.max_frame_time.is_finite() && self.max_frame_time > 0.0){
+            warn!(logger, "Invalid maximum frame time {}, using {}.", self.max_frame_time, default.max_frame_time);
+            self.max_frame_time = default.max_frame_time;
+        }
+    }
+}
+
+///The accumulator of the fixed timestep loop.
+struct Timestep{
+    step : f64,
+    max_frame_time : f64,
+    accumulator : f64,
+    last_frame : Instant,
+}
+impl Timestep{
+    fn new(config : &EngineConfig) -> Self{
+        return Self{
+            step : 1.0 / config.tick_rate,
+            max_frame_time : config.max_frame_time,
+            accumulator : 0.0,
+            last_frame : Instant::now(),
+        }
+    }
+    ///Measures the time since the last frame and adds it to the accumulator.
+    fn advance(&mut self) -> f64{
+        let now = Instant::now();
+        let frame_time = now.duration_since(self.last_frame).as_secs_f64();
+        self.last_frame = now;
+        self.accumulator += frame_time.min(self.max_frame_time);
+        return frame_time;
+    }
+    ///Takes one step out of the accumulator if a fixed update is due.
+    fn tick(&mut self) -> bool{
+        if self.accumulator < self.step{return false}
+        self.accumulator -= self.step;
+        return true;
+    }
+    ///How far the accumulator is into the next step, for interpolating between fixed updates.
+    fn alpha(&self) -> f64{
+        return self.accumulator / self.step;
+    }
+    fn until_next_tick(&self) -> Duration{
+        return Duration::from_secs_f64((self.step - self.accumulator).max(0.0));
+    }
+}
 
 pub struct EngineThread{
     receiver : Receiver<EngineTask>,
     sender : Sender<EngineResult>,
     logger : Logger,
-    event_loop : EventLoop<()>,
-    window : Window,
-    renderer : Renderer,
+    path_manager : PathManager,
+    config : EngineConfig,
+    window_config : WindowConfig,
+    exit : bool,
+    close_veto : bool,
+    subscribers : Subscribers,
+    ///The event loop, window and renderer are only created in windowed mode.
+    event_loop : Option<EventLoop<()>>,
+    window : Option<Window>,
+    renderer : Option<Renderer>,
+    ///The error the event loop stopped with, reported instead of a regular finish.
+    failure : Option<EngineError>,
+    world : World,
+    schedule : Schedule,
+    scenes : SceneLoader,
+    assets : AssetServer,
+    input : InputState,
+    shared : SharedState,
 }
 impl EngineThread{
-    pub fn new(sender : Sender<EngineResult>, receiver : Receiver<EngineTask>, logger : Logger, path_manager : PathManager) -> Self{
-        info!(logger, "[thread#{}]Creating new engine.", rayon::current_thread_index().unwrap());
-        let event_loop = EventLoop::new_any_thread();
-        let window = Window::new(&event_loop).unwrap();
-        let render_config = path_manager.load_file_or_default(&logger, "render", FileType::Config);
-        let render_instance = unsafe{RenderInstance::new(logger.clone(), &window, render_config)};
-        let renderer = Renderer::new(render_instance, path_manager);
-        return Self{
-            sender,receiver,logger,event_loop,window,renderer,
+    pub fn new(sender : Sender<EngineResult>, receiver : Receiver<EngineTask>, logger : Logger, path_manager : PathManager, options : EngineOptions, shared : SharedState, subscribers : Subscribers) -> Result<Self, EngineError>{
+        let mode = options.mode;
+        //Loggers handed to code running on other threads stay without the thread key of the engine thread.
+        let root_logger = logger.clone();
+        let logger = logger.new(o!("thread" => rayon::current_thread_index()));
+        info!(logger, "Creating new {} engine.", mode);
+        let mut config : EngineConfig = path_manager.load_file_or_default(&logger, "engine", FileType::Config);
+        config.validate(&logger);
+        let window_config : WindowConfig = path_manager.load_file_or_default(&logger, "window", FileType::Config);
+        let scenes = SceneLoader::new(logger.clone(), path_manager.clone());
+        Self::mount_assets(&logger, &path_manager, &config);
+        let assets = AssetServer::new(root_logger.clone(), path_manager.vfs().clone());
+        if config.hot_reload{
+            if let Err(error) = assets.watch(){warn!(logger, "Failed to watch the assets, hot reloading is disabled, {}.", error)}
+        }
+        let mut engine = Self{
+            sender,receiver,logger,path_manager,config,window_config,shared,subscribers,scenes,assets,
+            exit : false,
+            close_veto : false,
+            event_loop : None,
+            window : None,
+            renderer : None,
+            failure : None,
+            world : World::new(),
+            schedule : Schedule::new(),
+            input : InputState::default(),
+        };
+        engine.world.register::<Transform>();
+        engine.world.register::<GlobalTransform>();
+        engine.world.register::<Parent>();
+        engine.world.register::<Children>();
+        if mode == EngineMode::Windowed{
+            let event_loop = EventLoop::new_any_thread();
+            let window = engine.window_config.build(&event_loop).map_err(|error| EngineError::Window(error.to_string()))?;
+            if engine.window_config.cursor_grab{
+                if let Err(error) = window.set_cursor_grab(true){warn!(engine.logger, "Failed to grab the cursor, {}.", error)}
+            }
+            let render_config = engine.path_manager.load_file_or_default(&engine.logger, "render", FileType::Config);
+            crash::set_render_config(&render_config);
+            let render_instance = unsafe{RenderInstance::new(root_logger, engine.path_manager.app_name(), &options.app_version, &window, render_config, options.device_requirements)}?;
+            let renderer = Renderer::new(render_instance, engine.path_manager.clone())?;
+            crash::set_gpu(&renderer.capabilities().device_name);
+            *engine.shared.capabilities.write().unwrap() = Some(renderer.capabilities().clone());
+            engine.renderer = Some(renderer);
+            engine.event_loop = Some(event_loop);
+            engine.window = Some(window);
+        }
+        return Ok(engine);
+    }
+    ///Mounts the packed assets above the loose ones and an explicit source directory above everything.
+    fn mount_assets(logger : &Logger, path_manager : &PathManager, config : &EngineConfig){
+        let archive = path_manager.assets_archive();
+        if archive.exists(){
+            match Archive::open(&archive){
+                Ok(archive) => {path_manager.vfs().mount("assets", Arc::new(archive), priority::ARCHIVE)}
+                Err(error) => {warn!(logger, "Failed to open {:?}, using the loose assets, {}.", archive, error)}
+            }
+        }
+        if let Some(directory) = &config.asset_source{
+            path_manager.vfs().mount("assets", Arc::new(DirectorySource::new(directory.clone())), priority::OVERRIDE);
         }
     }
-    pub fn listen(self){
+    pub fn listen(mut self){
         while let Ok(task) = self.receiver.recv(){
             match task{
-                EngineTask::Start => {break;}
-                EngineTask::SetWindowName(name) => {self.window.set_title(&name); self.sender.send(EngineResult::Success).unwrap()}
+                EngineTask::Start(application, actions) => {return self.start(application, actions);}
+                EngineTask::Request(request) => {self.handle_request(request)}
             }
         }
-        self.start();
     }
-    fn start(mut self){
-        info!(self.logger, "[thread#{}]Starting the event loop", rayon::current_thread_index().unwrap());
-        self.event_loop.run_return(|event, _, control_flow|{
-            match event{
-                Event::WindowEvent {window_id : _, event} => {
+    fn poll_tasks(&mut self){
+        while let Ok(task) = self.receiver.try_recv(){
+            match task{
+                EngineTask::Request(request) => {self.handle_request(request)}
+                EngineTask::Start(..) => {warn!(self.logger, "The engine was already started.")}
+            }
+        }
+    }
+    fn handle_request(&mut self, request : Request){
+        let result = self.handle_command(request.command.clone());
+        if let Err(error) = &result{warn!(self.logger, "Command {} failed, {}.", request.id, error)}
+        request.reply(result);
+    }
+    ///Applies a command, window changes are mirrored into the window config.
+    fn handle_command(&mut self, command : EngineCommand) -> Result<Response, EngineError>{
+        match command{
+            EngineCommand::SetCloseVeto(veto) => {self.close_veto = veto; return Ok(Response::Done)}
+            EngineCommand::Exit => {self.exit = true; return Ok(Response::Done)}
+            _ => {}
+        }
+        let window = self.window.as_ref().ok_or(EngineError::Headless)?;
+        match command{
+            EngineCommand::SetWindowName(name) => {window.set_title(&name)}
+            EngineCommand::SetWindowSize(width, height) => {
+                window.set_inner_size(PhysicalSize::new(width, height));
+                self.window_config.width = width;
+                self.window_config.height = height;
+            }
+            EngineCommand::SetWindowPosition(x, y) => {
+                window.set_outer_position(PhysicalPosition::new(x, y));
+                self.window_config.position = Some((x, y));
+            }
+            EngineCommand::SetFullscreen(mode, monitor) => {
+                self.window_config.fullscreen = mode;
+                self.window_config.monitor = monitor;
+                window.set_fullscreen(self.window_config.fullscreen(window.available_monitors().collect(), window.primary_monitor()));
+            }
+            EngineCommand::SetResizable(resizable) => {window.set_resizable(resizable); self.window_config.resizable = resizable}
+            EngineCommand::SetDecorations(decorations) => {window.set_decorations(decorations); self.window_config.decorations = decorations}
+            EngineCommand::SetAlwaysOnTop(always_on_top) => {window.set_always_on_top(always_on_top); self.window_config.always_on_top = always_on_top}
+            EngineCommand::SetCursorGrab(grab) => {
+                window.set_cursor_grab(grab).map_err(|error| EngineError::Window(error.to_string()))?;
+                self.window_config.cursor_grab = grab;
+            }
+            EngineCommand::WindowConfig => {
+                let mut config = self.window_config.clone();
+                config.update_geometry(window);
+                return Ok(Response::WindowConfig(config));
+            }
+            EngineCommand::SetCloseVeto(_) | EngineCommand::Exit => {}
+        }
+        return Ok(Response::Done);
+    }
+    fn start(mut self, mut application : Box<dyn Application>, mut actions : ActionMap){
+        info!(self.logger, "Starting the event loop");
+        let mut timestep = Timestep::new(&self.config);
+        let mut context = Context{input : &self.input, actions : &mut actions, window : self.window.as_ref(), renderer : self.renderer.as_ref(), world : &mut self.world, schedule : &mut self.schedule, scenes : &mut self.scenes, assets : &self.assets, exit : false};
+        application.init(&mut context);
+        self.exit |= context.exit;
+        self.subscribers.publish(EngineEvent::Started);
+        match self.event_loop.take(){
+            Some(mut event_loop) => {
+                event_loop.run_return(|event, _, control_flow|{
+                    //Commands are polled on every event so they are answered while the loop runs.
+                    self.poll_tasks();
+                    if self.exit{*control_flow = ControlFlow::Exit; return;}
                     match event{
-                        WindowEvent::CloseRequested => {*control_flow = ControlFlow::Exit}
+                        Event::WindowEvent {window_id : _, event : WindowEvent::CloseRequested} => {
+                            self.subscribers.publish(EngineEvent::CloseRequested);
+                            if !self.close_veto{*control_flow = ControlFlow::Exit}
+                        }
+                        Event::WindowEvent {window_id : _, event} => {
+                            match &event{
+                                WindowEvent::Resized(size) => {self.subscribers.publish(EngineEvent::Resized(size.width, size.height))}
+                                WindowEvent::Focused(focused) => {self.subscribers.publish(EngineEvent::Focused(*focused))}
+                                WindowEvent::ScaleFactorChanged {scale_factor, ..} => {self.subscribers.publish(EngineEvent::ScaleFactorChanged(*scale_factor))}
+                                WindowEvent::DroppedFile(path) => {self.subscribers.publish(EngineEvent::FileDropped(path.clone()))}
+                                _ => {}
+                            }
+                            self.input.handle_window_event(&event);
+                        }
+                        Event::DeviceEvent {device_id : _, event} => {self.input.handle_device_event(&event)}
+                        Event::MainEventsCleared => {
+                            self.frame(application.as_mut(), &mut actions, &mut timestep);
+                            if self.exit{*control_flow = ControlFlow::Exit}
+                        }
                         _ => {}
                     }
+                });
+            }
+            None => {
+                //Without a window there are no events, the loop just sleeps until the next tick.
+                while !self.exit{
+                    self.poll_tasks();
+                    if self.exit{break;}
+                    self.frame(application.as_mut(), &mut actions, &mut timestep);
+                    std::thread::sleep(timestep.until_next_tick());
                 }
-                _ => {}
             }
-        });
-        info!(self.logger, "[thread#{}]Engine stopping.", rayon::current_thread_index().unwrap());
+        }
+        let mut context = Context{input : &self.input, actions : &mut actions, window : self.window.as_ref(), renderer : self.renderer.as_ref(), world : &mut self.world, schedule : &mut self.schedule, scenes : &mut self.scenes, assets : &self.assets, exit : false};
+        application.shutdown(&mut context);
+        if let Err(error) = self.path_manager.save_file("engine", FileType::Config, &self.config){warn!(self.logger, "Failed to save the engine config, {}.", error)}
+        if let Some(window) = &self.window{
+            self.window_config.update_geometry(window);
+            if let Err(error) = self.path_manager.save_file("window", FileType::Config, &self.window_config){warn!(self.logger, "Failed to save the window config, {}.", error)}
+        }
+        info!(self.logger, "Engine stopping.");
         drop(self.logger);
-        self.renderer.stop();
-        self.sender.send(EngineResult::Finished).unwrap();
+        if let Some(renderer) = &self.renderer{renderer.stop()}
+        self.subscribers.publish(EngineEvent::Stopped);
+        let _ = self.sender.send(match self.failure{
+            Some(error) => {EngineResult::Failed(error)}
+            None => {EngineResult::Finished}
+        });
+    }
+    ///Runs the due fixed updates and the frame update, then publishes the input and renderer state.
+    fn frame(&mut self, application : &mut dyn Application, actions : &mut ActionMap, timestep : &mut Timestep){
+        let frame_time = timestep.advance();
+        let mut context = Context{input : &self.input, actions, window : self.window.as_ref(), renderer : self.renderer.as_ref(), world : &mut self.world, schedule : &mut self.schedule, scenes : &mut self.scenes, assets : &self.assets, exit : false};
+        while !context.exit && timestep.tick(){
+            application.fixed_update(&mut context, timestep.step);
+            context.schedule.run(context.world);
+        }
+        if !context.exit{application.update(&mut context, frame_time, timestep.alpha())}
+        self.exit |= context.exit;
+        self.world.flush();
+        propagate_transforms(&self.world);
+        resolve_assets(&self.world, &self.assets);
+        self.assets.update();
+        //Without a renderer the GPU data of the assets is simply dropped.
+        for task in self.assets.take_gpu_tasks(){
+            match (task, &self.renderer){
+                (GpuTask::Upload(id, asset), Some(renderer)) => {renderer.upload(id, asset)}
+                (GpuTask::Release(id), Some(renderer)) => {renderer.release(id)}
+                _ => {}
+            }
+        }
+        for path in self.assets.take_reloaded(){
+            self.subscribers.publish(EngineEvent::AssetReloaded(path));
+        }
+        if let (Some(window), Some(renderer)) = (&self.window, &self.renderer){
+            let size = window.inner_size();
+            renderer.submit(extract(&self.world, size.width.max(1) as f32 / size.height.max(1) as f32));
+        }
+        *self.shared.input.write().unwrap() = self.input.clone();
+        self.input.end_frame();
+        while let Some(result) = self.renderer.as_mut().and_then(|renderer| renderer.poll()){
+            match result{
+                RenderResult::Error(error) => {self.subscribers.publish(EngineEvent::RenderError(error))}
+                RenderResult::DeviceLost => {self.subscribers.publish(EngineEvent::DeviceLost)}
+                RenderResult::DeviceRecovered(capabilities) => {
+                    crash::set_gpu(&capabilities.device_name);
+                    *self.shared.capabilities.write().unwrap() = Some(capabilities);
+                    self.subscribers.publish(EngineEvent::DeviceRecovered);
+                }
+                //The render thread is gone, so the engine stops with its error.
+                RenderResult::Failed(error) => {
+                    crit!(self.logger, "The renderer failed, {}.", error);
+                    self.failure = Some(EngineError::Render(error));
+                    self.exit = true;
+                }
+                _ => {}
+            }
+        }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn timestep() -> Timestep{
+        return Timestep::new(&EngineConfig{tick_rate : 10.0, max_frame_time : 0.25, ..Default::default()});
+    }
+
+    #[test]
+    fn due_steps_are_taken_from_the_accumulator(){
+        let mut timestep = timestep();
+        timestep.accumulator = 0.25;
+        assert!(timestep.tick() && timestep.tick() && !timestep.tick());
+        assert!((timestep.alpha() - 0.5).abs() < 1e-9);
+        assert!((timestep.until_next_tick().as_secs_f64() - 0.05).abs() < 1e-9);
+        timestep.accumulator = 0.1;
+        assert!(timestep.tick());
+        assert_eq!(timestep.until_next_tick(), Duration::from_secs_f64(0.1));
+    }
+    #[test]
+    fn long_frames_are_clamped(){
+        let mut timestep = timestep();
+        timestep.last_frame = Instant::now() - Duration::from_secs(2);
+        let frame_time = timestep.advance();
+        assert!(frame_time >= 2.0);
+        assert_eq!(timestep.accumulator, 0.25);
+        assert!(timestep.tick() && timestep.tick() && !timestep.tick());
+    }
+    #[test]
+    fn invalid_timings_are_replaced(){
+        let logger = Logger::root(slog::Discard, o!());
+        for (tick_rate, max_frame_time) in [(0.0, 0.0), (-60.0, -1.0), (f64::NAN, f64::INFINITY), (f64::INFINITY, f64::NAN)]{
+            let mut config = EngineConfig{tick_rate, max_frame_time, ..Default::default()};
+            config.validate(&logger);
+            assert_eq!((config.tick_rate, config.max_frame_time), (60.0, 0.25));
+            let mut timestep = Timestep::new(&config);
+            timestep.last_frame = Instant::now() - Duration::from_secs(1);
+            timestep.advance();
+            assert!(timestep.tick());
+            timestep.until_next_tick();
+        }
+        let mut config = EngineConfig{tick_rate : 30.0, max_frame_time : 0.1, ..Default::default()};
+        config.validate(&logger);
+        assert_eq!((config.tick_rate, config.max_frame_time), (30.0, 0.1));
+    }
+    #[test]
+    fn overdue_ticks_do_not_wait(){
+        let mut timestep = timestep();
+        timestep.accumulator = 0.3;
+        assert_eq!(timestep.until_next_tick(), Duration::ZERO);
+    }
+}
@@ -0,0 +1,71 @@
+use std::fmt::{Display, Formatter};
+use omage_renderer::error::RenderError;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum EngineError{
+    Window(String),
+    Render(RenderError),
+    Disconnected,
+    UnexpectedResponse,
+    Headless,
+    ///The engine thread panicked, with the panic message.
+    Panicked(String),
+}
+impl Display for EngineError{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return match self{
+            EngineError::Window(error) => {write!(f, "window error, {}", error)}
+            EngineError::Render(error) => {write!(f, "renderer error, {}", error)}
+            EngineError::Disconnected => {write!(f, "the engine thread stopped unexpectedly")}
+            EngineError::UnexpectedResponse => {write!(f, "the engine thread replied with the wrong response type")}
+            EngineError::Headless => {write!(f, "the engine runs without a window")}
+            EngineError::Panicked(message) => {write!(f, "the engine thread panicked, {}", message)}
+        }
+    }
+}
+impl std::error::Error for EngineError{}
+impl From<RenderError> for EngineError{
+    fn from(error : RenderError) -> Self {
+        return EngineError::Render(error);
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum SceneError{
+    ///The scene file is missing or not valid TOML, with the scene and error.
+    Load(String, String),
+    ///The scene file could not be written, with the scene and error.
+    Save(String, String),
+    ///A component of the scene could not be converted, with the scene, component and error.
+    Component(String, String, String),
+    ///An entity references a parent index outside of the scene.
+    Parent(String, usize),
+}
+impl Display for SceneError{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return match self{
+            SceneError::Load(scene, error) => {write!(f, "failed to load the scene {}, {}", scene, error)}
+            SceneError::Save(scene, error) => {write!(f, "failed to save the scene {}, {}", scene, error)}
+            SceneError::Component(scene, component, error) => {write!(f, "invalid component {} in the scene {}, {}", component, scene, error)}
+            SceneError::Parent(scene, parent) => {write!(f, "the scene {} references the missing parent {}", scene, parent)}
+        }
+    }
+}
+impl std::error::Error for SceneError{}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum AssetError{
+    ///The asset file could not be read, with the path and error.
+    Io(String, String),
+    ///The asset file was read but is not valid for its type, with the path and error.
+    Decode(String, String),
+}
+impl Display for AssetError{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return match self{
+            AssetError::Io(path, error) => {write!(f, "failed to read the asset {}, {}", path, error)}
+            AssetError::Decode(path, error) => {write!(f, "invalid asset {}, {}", path, error)}
+        }
+    }
+}
+impl std::error::Error for AssetError{}
@@ -0,0 +1,40 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use crossbeam_channel::{Receiver, Sender};
+use omage_renderer::error::RenderError;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum EngineEvent{
+    ///The application was initialized and the event loop is running.
+    Started,
+    ///The new inner size in physical pixels.
+    Resized(u32, u32),
+    Focused(bool),
+    ScaleFactorChanged(f64),
+    FileDropped(PathBuf),
+    ///The user asked to close the window, it only closes by itself while no close veto is set.
+    CloseRequested,
+    RenderError(RenderError),
+    DeviceLost,
+    DeviceRecovered,
+    ///A file under the assets directory changed, assets loaded from it were already replaced by the new version.
+    AssetReloaded(String),
+    Stopped,
+}
+
+///The subscribed event channels, shared between the engine handles and the engine thread.
+#[derive(Clone, Default)]
+pub struct Subscribers{
+    senders : Arc<Mutex<Vec<Sender<EngineEvent>>>>,
+}
+impl Subscribers{
+    pub fn subscribe(&self) -> Receiver<EngineEvent>{
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        self.senders.lock().unwrap().push(sender);
+        return receiver;
+    }
+    ///Sends the event to every subscriber, dropping the ones whose receiver is gone.
+    pub fn publish(&self, event : EngineEvent){
+        self.senders.lock().unwrap().retain(|sender| sender.send(event.clone()).is_ok());
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::HashSet;
+use winit::event::{DeviceEvent, ElementState, MouseScrollDelta, WindowEvent};
+pub use winit::event::{ModifiersState, MouseButton, VirtualKeyCode};
+
+///Pixels per scrolled line, used to report line and pixel based scrolling in the same unit.
+const PIXELS_PER_LINE : f64 = 20.0;
+
+///The input state of a single frame, collected from the window events by the engine thread.
+#[derive(Clone, Default, Debug)]
+pub struct InputState{
+    keys_down : HashSet<VirtualKeyCode>,
+    keys_pressed : HashSet<VirtualKeyCode>,
+    keys_released : HashSet<VirtualKeyCode>,
+    buttons_down : HashSet<MouseButton>,
+    buttons_pressed : HashSet<MouseButton>,
+    buttons_released : HashSet<MouseButton>,
+    cursor_position : (f64, f64),
+    mouse_delta : (f64, f64),
+    scroll_delta : (f64, f64),
+    modifiers : ModifiersState,
+    focused : bool,
+    text : String,
+}
+impl InputState{
+    pub fn handle_window_event(&mut self, event : &WindowEvent){
+        match event{
+            WindowEvent::KeyboardInput {input, ..} => {
+                if let Some(key) = input.virtual_keycode{
+                    match input.state{
+                        ElementState::Pressed => {
+                            if self.keys_down.insert(key){self.keys_pressed.insert(key);}
+                        }
+                        ElementState::Released => {
+                            if self.keys_down.remove(&key){self.keys_released.insert(key);}
+                        }
+                    }
+                }
+            }
+            WindowEvent::MouseInput {state, button, ..} => {
+                match state{
+                    ElementState::Pressed => {
+                        if self.buttons_down.insert(*button){self.buttons_pressed.insert(*button);}
+                    }
+                    ElementState::Released => {
+                        if self.buttons_down.remove(button){self.buttons_released.insert(*button);}
+                    }
+                }
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {self.modifiers = *modifiers}
+            WindowEvent::CursorMoved {position, ..} => {self.cursor_position = (position.x, position.y)}
+            WindowEvent::MouseWheel {delta, ..} => {
+                let (x, y) = match delta{
+                    MouseScrollDelta::LineDelta(x, y) => {(*x as f64 * PIXELS_PER_LINE, *y as f64 * PIXELS_PER_LINE)}
+                    MouseScrollDelta::PixelDelta(position) => {(position.x, position.y)}
+                };
+                self.scroll_delta.0 += x;
+                self.scroll_delta.1 += y;
+            }
+            WindowEvent::Focused(focused) => {
+                self.focused = *focused;
+                //Releases are not delivered to an unfocused window, so everything held is released here.
+                if !focused{
+                    self.keys_released.extend(self.keys_down.drain());
+                    self.buttons_released.extend(self.buttons_down.drain());
+                    self.modifiers = ModifiersState::empty();
+                }
+            }
+            WindowEvent::ReceivedCharacter(character) if !character.is_control() => {self.text.push(*character)}
+            _ => {}
+        }
+    }
+    pub fn handle_device_event(&mut self, event : &DeviceEvent){
+        if let DeviceEvent::MouseMotion {delta} = event{
+            self.mouse_delta.0 += delta.0;
+            self.mouse_delta.1 += delta.1;
+        }
+    }
+    ///Clears the per frame state, called once the frame's snapshot was published.
+    pub fn end_frame(&mut self){
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+        self.mouse_delta = (0.0, 0.0);
+        self.scroll_delta = (0.0, 0.0);
+        self.text.clear();
+    }
+    pub fn is_pressed(&self, key : VirtualKeyCode) -> bool{
+        return self.keys_down.contains(&key);
+    }
+    pub fn just_pressed(&self, key : VirtualKeyCode) -> bool{
+        return self.keys_pressed.contains(&key);
+    }
+    pub fn just_released(&self, key : VirtualKeyCode) -> bool{
+        return self.keys_released.contains(&key);
+    }
+    pub fn is_mouse_pressed(&self, button : MouseButton) -> bool{
+        return self.buttons_down.contains(&button);
+    }
+    pub fn mouse_just_pressed(&self, button : MouseButton) -> bool{
+        return self.buttons_pressed.contains(&button);
+    }
+    pub fn mouse_just_released(&self, button : MouseButton) -> bool{
+        return self.buttons_released.contains(&button);
+    }
+    pub fn cursor_position(&self) -> (f64, f64){
+        return self.cursor_position;
+    }
+    pub fn mouse_delta(&self) -> (f64, f64){
+        return self.mouse_delta;
+    }
+    pub fn scroll_delta(&self) -> (f64, f64){
+        return self.scroll_delta;
+    }
+    pub fn modifiers(&self) -> ModifiersState{
+        return self.modifiers;
+    }
+    pub fn focused(&self) -> bool{
+        return self.focused;
+    }
+    ///The text typed during the frame.
+    pub fn text(&self) -> &str{
+        return &self.text;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests{
+    use winit::event::{DeviceId, KeyboardInput};
+    use super::*;
+
+    #[allow(deprecated)]
+    pub(crate) fn key(input : &mut InputState, key : VirtualKeyCode, state : ElementState){
+        let device_id = unsafe{DeviceId::dummy()};
+        input.handle_window_event(&WindowEvent::KeyboardInput{device_id, input : KeyboardInput{scancode : 0, state, virtual_keycode : Some(key), modifiers : ModifiersState::empty()}, is_synthetic : false});
+    }
+    #[allow(deprecated)]
+    pub(crate) fn button(input : &mut InputState, button : MouseButton, state : ElementState){
+        let device_id = unsafe{DeviceId::dummy()};
+        input.handle_window_event(&WindowEvent::MouseInput{device_id, state, button, modifiers : ModifiersState::empty()});
+    }
+
+    #[test]
+    fn keys_are_pressed_held_and_released(){
+        let mut input = InputState::default();
+        key(&mut input, VirtualKeyCode::A, ElementState::Pressed);
+        assert!(input.is_pressed(VirtualKeyCode::A) && input.just_pressed(VirtualKeyCode::A) && !input.just_released(VirtualKeyCode::A));
+        input.end_frame();
+        //Key repeat sends more presses, which do not count as new ones.
+        key(&mut input, VirtualKeyCode::A, ElementState::Pressed);
+        assert!(input.is_pressed(VirtualKeyCode::A) && !input.just_pressed(VirtualKeyCode::A));
+        key(&mut input, VirtualKeyCode::A, ElementState::Released);
+        assert!(!input.is_pressed(VirtualKeyCode::A) && input.just_released(VirtualKeyCode::A));
+        input.end_frame();
+        assert!(!input.just_released(VirtualKeyCode::A));
+    }
+    #[test]
+    fn a_press_and_release_within_a_frame_is_seen(){
+        let mut input = InputState::default();
+        button(&mut input, MouseButton::Left, ElementState::Pressed);
+        button(&mut input, MouseButton::Left, ElementState::Released);
+        assert!(!input.is_mouse_pressed(MouseButton::Left));
+        assert!(input.mouse_just_pressed(MouseButton::Left) && input.mouse_just_released(MouseButton::Left));
+        //A release without a press, like one that started outside of the window, is ignored.
+        button(&mut input, MouseButton::Right, ElementState::Released);
+        assert!(!input.mouse_just_released(MouseButton::Right));
+    }
+    #[test]
+    fn losing_focus_releases_everything(){
+        let mut input = InputState::default();
+        key(&mut input, VirtualKeyCode::W, ElementState::Pressed);
+        button(&mut input, MouseButton::Left, ElementState::Pressed);
+        input.handle_window_event(&WindowEvent::ModifiersChanged(ModifiersState::SHIFT));
+        input.end_frame();
+        input.handle_window_event(&WindowEvent::Focused(false));
+        assert!(!input.is_pressed(VirtualKeyCode::W) && input.just_released(VirtualKeyCode::W));
+        assert!(!input.is_mouse_pressed(MouseButton::Left) && input.mouse_just_released(MouseButton::Left));
+        assert_eq!(input.modifiers(), ModifiersState::empty());
+        assert!(!input.focused());
+    }
+    #[test]
+    fn deltas_accumulate_until_the_frame_ends(){
+        let mut input = InputState::default();
+        input.handle_device_event(&DeviceEvent::MouseMotion{delta : (1.0, 2.0)});
+        input.handle_device_event(&DeviceEvent::MouseMotion{delta : (3.0, -1.0)});
+        let device_id = unsafe{DeviceId::dummy()};
+        #[allow(deprecated)]
+        input.handle_window_event(&WindowEvent::MouseWheel{device_id, delta : MouseScrollDelta::LineDelta(0.0, 1.0), phase : winit::event::TouchPhase::Moved, modifiers : ModifiersState::empty()});
+        input.handle_window_event(&WindowEvent::ReceivedCharacter('a'));
+        input.handle_window_event(&WindowEvent::ReceivedCharacter('\u{8}'));
+        assert_eq!(input.mouse_delta(), (4.0, 1.0));
+        assert_eq!(input.scroll_delta(), (0.0, PIXELS_PER_LINE));
+        assert_eq!(input.text(), "a");
+        input.end_frame();
+        assert_eq!(input.mouse_delta(), (0.0, 0.0));
+        assert_eq!(input.scroll_delta(), (0.0, 0.0));
+        assert_eq!(input.text(), "");
+    }
+}
@@ -1,56 +1,169 @@
+#![allow(clippy::needless_return)]
 pub mod engine;
+pub mod action;
+pub mod asset;
+pub mod command;
+pub mod application;
+mod crash;
+pub mod error;
+pub mod event;
+pub mod input;
+pub mod scene;
+pub mod window;
 
+use std::panic::AssertUnwindSafe;
 use crossbeam_channel::{Receiver, Sender};
-use slog::{crit, info, Logger};
-use omage_util::PathManager;
+use std::fmt::{Display, Formatter};
+use slog::{crit, info, o, warn, Logger};
+use omage_renderer::capabilities::{DeviceCapabilities, DeviceRequirements};
+use omage_util::{panic_message, PathManager, PathOptions};
+use crate::action::ActionMap;
+use crate::command::{EngineHandle, Request, SharedState};
+use crate::application::Application;
 use crate::engine::EngineThread;
+use crate::error::EngineError;
+use crate::event::{EngineEvent, Subscribers};
+use crate::input::InputState;
 
 pub struct Engine{
     logger : Logger,
     sender : Sender<EngineTask>,
+    handle : EngineHandle,
     receiver : Receiver<EngineResult>,
+    actions : ActionMap,
 }
 impl Engine{
-    pub fn new(app_name : &str) -> Self{
-        let path_manager = PathManager::new(app_name);
+    pub fn new(app_name : &str, mode : EngineMode) -> Result<Self, EngineError>{
+        return Self::with_options(app_name, EngineOptions::new(mode));
+    }
+    pub fn with_options(app_name : &str, options : EngineOptions) -> Result<Self, EngineError>{
+        let mode = options.mode;
+        let path_manager = PathManager::with_options(app_name, options.path_options.clone());
         let logger = path_manager.create_logger();
         info!(logger, "Started the logger.");
-        let (sender, thread_receiver) = crossbeam_channel::bounded(1);
+        crash::install(logger.clone(), path_manager.logs_directory(), mode.to_string());
+        info!(logger, "Using the config directory {:?}, the cache directory {:?} and the data directory {:?}{}.", path_manager.config_directory(), path_manager.cache_directory(), path_manager.data_directory(), if path_manager.is_portable() {" in portable mode"} else {""});
+        let (sender, thread_receiver) = crossbeam_channel::unbounded();
         let (thread_sender, receiver) = crossbeam_channel::bounded(1);
+        let actions = ActionMap::new(logger.clone(), path_manager.clone());
         let thread_logger = logger.clone(); let thread_path_manager = path_manager.clone();
-        rayon::spawn(|| {
-            let engine = EngineThread::new(thread_sender, thread_receiver, thread_logger, thread_path_manager);
-            engine.listen();
+        let shared = SharedState::default(); let thread_shared = shared.clone();
+        let subscribers = Subscribers::default(); let thread_subscribers = subscribers.clone();
+        rayon::spawn(move || {
+            let panic_sender = thread_sender.clone();
+            //A panic would abort the process on the rayon pool, the panic hook already reported it, so only the error is passed on.
+            let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                match EngineThread::new(thread_sender.clone(), thread_receiver, thread_logger.clone(), thread_path_manager, options, thread_shared, thread_subscribers){
+                    Ok(engine) => {
+                        let _ = thread_sender.send(EngineResult::Initialized);
+                        engine.listen();
+                    }
+                    Err(error) => {
+                        crit!(thread_logger.new(o!("thread" => rayon::current_thread_index())), "Failed to create the engine, {}.", error);
+                        let _ = thread_sender.send(EngineResult::Failed(error));
+                    }
+                }
+            }));
+            if let Err(payload) = result{let _ = panic_sender.send(EngineResult::Failed(EngineError::Panicked(panic_message(payload.as_ref()))));}
         });
-        return Self{
-            logger,sender,receiver,
+        let handle = EngineHandle::new(sender.clone(), subscribers, shared);
+        if mode == EngineMode::Headless{
+            //A dedicated server is usually stopped with a signal, which is turned into an exit command.
+            let signal_handle = handle.clone();
+            if let Err(error) = ctrlc::set_handler(move || {let _ = signal_handle.exit().send();}){
+                warn!(logger, "Failed to install the signal handler, {}.", error);
+            }
         }
+        return match receiver.recv(){
+            Ok(EngineResult::Initialized) => {Ok(Self{logger,sender,handle,receiver,actions})}
+            Ok(EngineResult::Failed(error)) => {Err(error)}
+            _ => {Err(EngineError::Disconnected)}
+        }
+    }
+    ///A handle for sending commands to the engine thread, also while `dispatch` blocks.
+    pub fn handle(&self) -> &EngineHandle{
+        return &self.handle;
     }
-    pub fn set_window_name(&self, name : String){
-        self.sender.send(EngineTask::SetWindowName(name.clone())).unwrap();
-        self.receiver.recv().unwrap();
-        info!(self.logger, "Changed window title to {}.", name);
+    ///Returns a channel receiving every engine event published from now on.
+    pub fn subscribe(&self) -> Receiver<EngineEvent>{
+        return self.handle.subscribe();
     }
-    pub fn dispatch(self){
-        if self.sender.send(EngineTask::Start).is_err(){crit!(self.logger, "Failed to start the engine")};
+    ///A snapshot of the input state of the last finished frame, `handle().input()` also reads it while `dispatch` blocks.
+    pub fn input(&self) -> InputState{
+        return self.handle.input();
+    }
+    ///The optional device features and extensions the renderer enabled, `None` in headless mode.
+    pub fn capabilities(&self) -> Option<DeviceCapabilities>{
+        return self.handle.capabilities();
+    }
+    pub fn actions(&self) -> &ActionMap{
+        return &self.actions;
+    }
+    pub fn actions_mut(&mut self) -> &mut ActionMap{
+        return &mut self.actions;
+    }
+    ///Hands the application to the engine thread and blocks until the event loop finished.
+    pub fn dispatch<A : Application + 'static>(self, application : A) -> Result<(), EngineError>{
+        if self.sender.send(EngineTask::Start(Box::new(application), self.actions)).is_err(){crit!(self.logger, "Failed to start the engine"); return Err(EngineError::Disconnected)};
         while let Ok(result) = self.receiver.recv(){
             match result{
-                EngineResult::Started => {info!(self.logger, "Successfully started the engine")}
-                EngineResult::Finished => {break}
+                EngineResult::Finished => {
+                    info!(self.logger, "Engine stopped.");
+                    return Ok(());
+                }
+                EngineResult::Failed(error) => {
+                    crit!(self.logger, "Engine stopped, {}.", error);
+                    return Err(error);
+                }
                 _ => {}
             }
         }
-        info!(self.logger, "Engine stopped.");
+        return Err(EngineError::Disconnected);
+    }
+}
+///How the engine is created, `Engine::new` uses the defaults for everything but the mode.
+#[derive(Clone)]
+pub struct EngineOptions{
+    pub mode : EngineMode,
+    ///The major.minor.patch version of the application, reported to the graphics driver.
+    pub app_version : String,
+    ///The device features and extensions the renderer requires or would like to use.
+    pub device_requirements : DeviceRequirements,
+    ///The directory overrides, read from the environment by default, binaries can add their flags with `PathOptions::from_args`.
+    pub path_options : PathOptions,
+}
+impl EngineOptions{
+    pub fn new(mode : EngineMode) -> Self{
+        return Self{
+            mode,
+            app_version : "0.0.0".to_owned(),
+            device_requirements : DeviceRequirements::default(),
+            path_options : PathOptions::from_environment(),
+        }
+    }
+}
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum EngineMode{
+    ///A window with a renderer, driven by the winit event loop.
+    Windowed,
+    ///No window, renderer or input, only the fixed timestep loop.
+    Headless,
+}
+impl Display for EngineMode{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return match self{
+            EngineMode::Windowed => {write!(f, "windowed")}
+            EngineMode::Headless => {write!(f, "headless")}
+        }
     }
 }
-#[derive(Clone, PartialEq)]
 pub enum EngineTask{
-    Start,
-    SetWindowName(String),
+    Start(Box<dyn Application>, ActionMap),
+    Request(Request),
 }
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Clone, PartialEq)]
 pub enum EngineResult{
+    Initialized,
+    Failed(EngineError),
     Finished,
-    Started,
-    Success,
 }
\ No newline at end of file
@@ -0,0 +1,94 @@
+use std::any::Any;
+use std::collections::HashMap;
+use crate::scene::Entity;
+
+///Anything that can be attached to an entity.
+pub trait Component : Send + Sync + 'static{}
+impl<T : Send + Sync + 'static> Component for T{}
+
+///A sparse set of one component type, the components are packed so iterating them is cache friendly.
+pub struct Storage<T : Component>{
+    components : Vec<T>,
+    entities : Vec<Entity>,
+    indices : HashMap<u32, usize>,
+}
+impl<T : Component> Default for Storage<T>{
+    fn default() -> Self {
+        return Self{
+            components : vec![],
+            entities : vec![],
+            indices : HashMap::new(),
+        }
+    }
+}
+impl<T : Component> Storage<T>{
+    ///Inserts the component, returning the one it replaced.
+    pub fn insert(&mut self, entity : Entity, component : T) -> Option<T>{
+        if let Some(&index) = self.indices.get(&entity.index){
+            self.entities[index] = entity;
+            return Some(std::mem::replace(&mut self.components[index], component));
+        }
+        self.indices.insert(entity.index, self.components.len());
+        self.components.push(component);
+        self.entities.push(entity);
+        return None;
+    }
+    pub fn remove(&mut self, entity : Entity) -> Option<T>{
+        let index = self.index(entity)?;
+        self.indices.remove(&entity.index);
+        let component = self.components.swap_remove(index);
+        self.entities.swap_remove(index);
+        if let Some(moved) = self.entities.get(index){self.indices.insert(moved.index, index);}
+        return Some(component);
+    }
+    pub fn get(&self, entity : Entity) -> Option<&T>{
+        return self.index(entity).map(|index| &self.components[index]);
+    }
+    pub fn get_mut(&mut self, entity : Entity) -> Option<&mut T>{
+        return self.index(entity).map(|index| &mut self.components[index]);
+    }
+    pub fn contains(&self, entity : Entity) -> bool{
+        return self.index(entity).is_some();
+    }
+    pub fn len(&self) -> usize{
+        return self.components.len();
+    }
+    pub fn is_empty(&self) -> bool{
+        return self.components.is_empty();
+    }
+    pub fn entities(&self) -> &[Entity]{
+        return &self.entities;
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)>{
+        return self.entities.iter().copied().zip(self.components.iter());
+    }
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)>{
+        return self.entities.iter().copied().zip(self.components.iter_mut());
+    }
+    ///The packed components, in the same order as `entities`, for use with rayon's parallel iterators.
+    pub fn components_mut(&mut self) -> (&[Entity], &mut [T]){
+        return (&self.entities, &mut self.components);
+    }
+    fn index(&self, entity : Entity) -> Option<usize>{
+        let index = *self.indices.get(&entity.index)?;
+        return if self.entities[index] == entity {Some(index)} else {None};
+    }
+}
+
+///The type erased interface the world uses to manage storages it does not know the type of.
+pub(crate) trait AnyStorage : Send + Sync{
+    fn remove_entity(&mut self, entity : Entity);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+impl<T : Component> AnyStorage for Storage<T>{
+    fn remove_entity(&mut self, entity : Entity){
+        self.remove(entity);
+    }
+    fn as_any(&self) -> &dyn Any{
+        return self;
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any{
+        return self;
+    }
+}
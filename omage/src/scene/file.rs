@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use slog::{warn, Logger};
+use omage_util::{FileType, PathManager};
+use omage_util::config::Versioned;
+use crate::asset::AssetServer;
+use crate::error::SceneError;
+use crate::scene::{Entity, World};
+use crate::scene::component::Component;
+use crate::scene::render::{Camera, Mesh, Sprite};
+use crate::scene::transform::Transform;
+
+///A scene as stored under assets/scenes, entities reference their parent by their position in the file.
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneFile{
+    pub entities : Vec<EntityData>,
+}
+impl Versioned for SceneFile{}
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EntityData{
+    pub parent : Option<usize>,
+    pub components : BTreeMap<String, toml::Value>,
+}
+
+///Components found in a scene file whose type was not registered, kept so saving writes them back unchanged.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct UnknownComponents(pub BTreeMap<String, toml::Value>);
+
+type AssetPaths = Box<dyn Fn(&World, Entity) -> Vec<String> + Send + Sync>;
+
+struct SceneComponent{
+    name : String,
+    load : fn(&mut World, Entity, toml::Value) -> Result<(), String>,
+    save : fn(&World, Entity) -> Option<Result<toml::Value, String>>,
+    has : fn(&World, Entity) -> bool,
+    assets : AssetPaths,
+}
+
+///The component types that can be stored in scene files, keyed by the name used in the file.
+pub struct SceneLoader{
+    logger : Logger,
+    path_manager : PathManager,
+    components : Vec<SceneComponent>,
+}
+impl SceneLoader{
+    pub fn new(logger : Logger, path_manager : PathManager) -> Self{
+        let mut loader = Self{logger, path_manager, components : vec![]};
+        loader.register::<Transform>("transform");
+        loader.register_with_assets::<Mesh>("mesh", |mesh| vec![mesh.path.clone()]);
+        loader.register_with_assets::<Sprite>("sprite", |sprite| vec![sprite.texture.clone()]);
+        loader.register::<Camera>("camera");
+        return loader;
+    }
+    pub fn register<T : Component + serde::Serialize + DeserializeOwned + Clone>(&mut self, name : &str){
+        self.register_with_assets::<T>(name, |_| vec![]);
+    }
+    ///Registers a component that references assets by their path relative to the assets directory.
+    pub fn register_with_assets<T : Component + serde::Serialize + DeserializeOwned + Clone>(&mut self, name : &str, assets : fn(&T) -> Vec<String>){
+        self.components.retain(|component| component.name != name);
+        self.components.push(SceneComponent{
+            name : name.to_owned(),
+            load : |world, entity, value| {
+                let component : T = value.try_into().map_err(|error : toml::de::Error| error.to_string())?;
+                world.insert(entity, component);
+                return Ok(());
+            },
+            save : |world, entity| world.get::<T>(entity).map(|component| toml::Value::try_from(component).map_err(|error| error.to_string())),
+            has : |world, entity| world.has::<T>(entity),
+            assets : Box::new(move |world, entity| world.get::<T>(entity).map(|component| assets(&component)).unwrap_or_default()),
+        });
+    }
+    ///Spawns the entities of a scene file into the world and returns them in file order, missing assets are only warned about.
+    ///On an error the entities spawned so far are despawned again, leaving the world as it was.
+    pub fn load(&self, world : &mut World, assets : &AssetServer, name : &str) -> Result<Vec<Entity>, SceneError>{
+        let file : SceneFile = self.path_manager.try_load_file(&self.logger, name, FileType::Scene).map_err(|error| SceneError::Load(name.to_owned(), error))?;
+        let entities : Vec<Entity> = file.entities.iter().map(|_| world.spawn()).collect();
+        if let Err(error) = self.insert_components(world, name, file, &entities){
+            for entity in &entities{world.despawn(*entity);}
+            return Err(error);
+        }
+        for entity in &entities{
+            for component in &self.components{
+                for path in (component.assets)(world, *entity){
+                    if !assets.exists(&path){warn!(self.logger, "Scene {} references the missing asset {}.", name, path)}
+                }
+            }
+        }
+        return Ok(entities);
+    }
+    fn insert_components(&self, world : &mut World, name : &str, file : SceneFile, entities : &[Entity]) -> Result<(), SceneError>{
+        for (data, &entity) in file.entities.into_iter().zip(entities.iter()){
+            let mut unknown = BTreeMap::new();
+            for (component_name, value) in data.components{
+                match self.components.iter().find(|component| component.name == component_name){
+                    Some(component) => {(component.load)(world, entity, value).map_err(|error| SceneError::Component(name.to_owned(), component_name, error))?}
+                    None => {unknown.insert(component_name, value);}
+                }
+            }
+            if !unknown.is_empty(){
+                warn!(self.logger, "Scene {} uses unregistered components {:?}, keeping them unchanged.", name, unknown.keys().collect::<Vec<_>>());
+                world.insert(entity, UnknownComponents(unknown));
+            }
+            if let Some(parent) = data.parent{
+                let parent = *entities.get(parent).ok_or_else(|| SceneError::Parent(name.to_owned(), parent))?;
+                world.set_parent(entity, parent);
+            }
+        }
+        return Ok(());
+    }
+    ///Writes every entity holding a registered component to the scene file.
+    ///The file lands in the assets of the data directory, which shadow the shipped scene, or in the asset source directory when one is configured.
+    pub fn save(&self, world : &World, name : &str) -> Result<(), SceneError>{
+        let entities : Vec<Entity> = world.entities().filter(|entity| self.is_saved(world, *entity)).collect();
+        let indices : BTreeMap<Entity, usize> = entities.iter().enumerate().map(|(index, entity)| (*entity, index)).collect();
+        let mut file = SceneFile::default();
+        for entity in &entities{
+            let mut components = world.get::<UnknownComponents>(*entity).map(|unknown| unknown.0).unwrap_or_default();
+            for component in &self.components{
+                if let Some(value) = (component.save)(world, *entity){
+                    components.insert(component.name.clone(), value.map_err(|error| SceneError::Component(name.to_owned(), component.name.clone(), error))?);
+                }
+            }
+            file.entities.push(EntityData{
+                parent : world.parent(*entity).and_then(|parent| indices.get(&parent).copied()),
+                components,
+            });
+        }
+        return self.path_manager.save_file(name, FileType::Scene, &file).map_err(|error| SceneError::Save(name.to_owned(), error.to_string()));
+    }
+    fn is_saved(&self, world : &World, entity : Entity) -> bool{
+        return world.has::<UnknownComponents>(entity) || self.components.iter().any(|component| (component.has)(world, entity));
+    }
+}
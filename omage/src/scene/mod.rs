@@ -0,0 +1,138 @@
+pub mod component;
+pub mod query;
+pub mod system;
+pub mod transform;
+pub mod render;
+pub mod file;
+
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use serde_derive::{Deserialize, Serialize};
+use crate::scene::component::{AnyStorage, Component, Storage};
+
+///A handle to an entity, the generation tells apart entities that reused the same slot.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub struct Entity{
+    pub index : u32,
+    pub generation : u32,
+}
+
+type Deferred = Box<dyn FnOnce(&mut World) + Send>;
+
+///The entities and their components, every component type lives in its own lock so systems touching different types run in parallel.
+#[derive(Default)]
+pub struct World{
+    generations : Vec<u32>,
+    alive : Vec<bool>,
+    free : Vec<u32>,
+    storages : HashMap<TypeId, RwLock<Box<dyn AnyStorage>>>,
+    deferred : Mutex<Vec<Deferred>>,
+}
+impl World{
+    pub fn new() -> Self{
+        return Self::default();
+    }
+    pub fn spawn(&mut self) -> Entity{
+        if let Some(index) = self.free.pop(){
+            self.alive[index as usize] = true;
+            return Entity{index, generation : self.generations[index as usize]};
+        }
+        self.generations.push(0);
+        self.alive.push(true);
+        return Entity{index : self.generations.len() as u32 - 1, generation : 0};
+    }
+    ///Removes the entity and its components, its children lose their parent.
+    pub fn despawn(&mut self, entity : Entity) -> bool{
+        if !self.is_alive(entity){return false}
+        self.detach(entity);
+        for storage in self.storages.values_mut(){
+            storage.get_mut().unwrap().remove_entity(entity);
+        }
+        self.alive[entity.index as usize] = false;
+        self.generations[entity.index as usize] += 1;
+        self.free.push(entity.index);
+        return true;
+    }
+    pub fn is_alive(&self, entity : Entity) -> bool{
+        return self.alive.get(entity.index as usize) == Some(&true) && self.generations[entity.index as usize] == entity.generation;
+    }
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_{
+        return self.alive.iter().enumerate().filter(|(_, alive)| **alive).map(|(index, _)| Entity{index : index as u32, generation : self.generations[index]});
+    }
+    ///Creates the storage of a component type, so it can be read before any component was inserted.
+    pub fn register<T : Component>(&mut self){
+        self.storages.entry(TypeId::of::<T>()).or_insert_with(|| RwLock::new(Box::<Storage<T>>::default()));
+    }
+    pub fn is_registered<T : Component>(&self) -> bool{
+        return self.storages.contains_key(&TypeId::of::<T>());
+    }
+    pub(crate) fn register_id(&mut self, id : TypeId, create : fn() -> Box<dyn AnyStorage>){
+        self.storages.entry(id).or_insert_with(|| RwLock::new(create()));
+    }
+    pub fn insert<T : Component>(&mut self, entity : Entity, component : T) -> Option<T>{
+        if !self.is_alive(entity){return None}
+        self.register::<T>();
+        return self.write::<T>().insert(entity, component);
+    }
+    pub fn remove<T : Component>(&mut self, entity : Entity) -> Option<T>{
+        if !self.is_registered::<T>(){return None}
+        return self.write::<T>().remove(entity);
+    }
+    pub fn get<T : Component + Clone>(&self, entity : Entity) -> Option<T>{
+        if !self.is_registered::<T>(){return None}
+        return self.read::<T>().get(entity).cloned();
+    }
+    pub fn has<T : Component>(&self, entity : Entity) -> bool{
+        return self.is_registered::<T>() && self.read::<T>().contains(entity);
+    }
+    ///Locks the storage of a component type for reading, panics if the type was never registered.
+    pub fn read<T : Component>(&self) -> Read<'_, T>{
+        let guard = self.storage::<T>().read().unwrap();
+        return Read{guard, _marker : std::marker::PhantomData};
+    }
+    ///Locks the storage of a component type for writing, panics if the type was never registered.
+    pub fn write<T : Component>(&self) -> Write<'_, T>{
+        let guard = self.storage::<T>().write().unwrap();
+        return Write{guard, _marker : std::marker::PhantomData};
+    }
+    ///Queues a structural change, like spawning or despawning, to be applied once the running systems finished.
+    pub fn defer<F : FnOnce(&mut World) + Send + 'static>(&self, change : F){
+        self.deferred.lock().unwrap().push(Box::new(change));
+    }
+    ///Applies the queued structural changes.
+    pub fn flush(&mut self){
+        let deferred = std::mem::take(self.deferred.get_mut().unwrap());
+        for change in deferred{change(self)}
+    }
+    fn storage<T : Component>(&self) -> &RwLock<Box<dyn AnyStorage>>{
+        return self.storages.get(&TypeId::of::<T>()).unwrap_or_else(|| panic!("the component type {} was never registered", std::any::type_name::<T>()));
+    }
+}
+
+pub struct Read<'a, T : Component>{
+    guard : RwLockReadGuard<'a, Box<dyn AnyStorage>>,
+    _marker : std::marker::PhantomData<T>,
+}
+impl<'a, T : Component> Deref for Read<'a, T>{
+    type Target = Storage<T>;
+    fn deref(&self) -> &Self::Target {
+        return self.guard.as_any().downcast_ref().unwrap();
+    }
+}
+pub struct Write<'a, T : Component>{
+    guard : RwLockWriteGuard<'a, Box<dyn AnyStorage>>,
+    _marker : std::marker::PhantomData<T>,
+}
+impl<'a, T : Component> Deref for Write<'a, T>{
+    type Target = Storage<T>;
+    fn deref(&self) -> &Self::Target {
+        return self.guard.as_any().downcast_ref().unwrap();
+    }
+}
+impl<'a, T : Component> DerefMut for Write<'a, T>{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        return self.guard.as_any_mut().downcast_mut().unwrap();
+    }
+}
@@ -0,0 +1,19 @@
+use crate::scene::Entity;
+use crate::scene::component::{Component, Storage};
+
+///Iterates the entities that have both components.
+pub fn join<'a, A : Component, B : Component>(a : &'a Storage<A>, b : &'a Storage<B>) -> impl Iterator<Item = (Entity, &'a A, &'a B)>{
+    return a.iter().filter_map(move |(entity, a)| b.get(entity).map(|b| (entity, a, b)));
+}
+///Iterates the entities that have both components, handing out the second one mutably.
+pub fn join_mut<'a, A : Component, B : Component>(a : &'a Storage<A>, b : &'a mut Storage<B>) -> impl Iterator<Item = (Entity, &'a A, &'a mut B)>{
+    return b.iter_mut().filter_map(move |(entity, b)| a.get(entity).map(|a| (entity, a, b)));
+}
+///Iterates the entities that have all three components, handing out the third one mutably.
+pub fn join3_mut<'a, A : Component, B : Component, C : Component>(a : &'a Storage<A>, b : &'a Storage<B>, c : &'a mut Storage<C>) -> impl Iterator<Item = (Entity, &'a A, &'a B, &'a mut C)>{
+    return c.iter_mut().filter_map(move |(entity, c)| Some((entity, a.get(entity)?, b.get(entity)?, c)));
+}
+///Iterates the components of the entities that do not have the excluded component.
+pub fn without<'a, A : Component, B : Component>(a : &'a Storage<A>, excluded : &'a Storage<B>) -> impl Iterator<Item = (Entity, &'a A)>{
+    return a.iter().filter(move |(entity, _)| !excluded.contains(*entity));
+}
@@ -0,0 +1,129 @@
+use glam::{Mat4, Vec2, Vec4};
+use serde_derive::{Deserialize, Serialize};
+use omage_renderer::scene::{CameraView, MeshInstance, RenderScene, SpriteInstance};
+use crate::asset::{AssetServer, Handle};
+use crate::asset::types::{MeshData, Texture};
+use crate::scene::World;
+use crate::scene::query::join;
+use crate::scene::transform::GlobalTransform;
+
+///The handles are filled in by the engine from the paths, which are relative to the assets directory.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Mesh{
+    pub path : String,
+    #[serde(skip)]
+    pub handle : Option<Handle<MeshData>>,
+}
+impl Mesh{
+    pub fn new(path : &str) -> Self{
+        return Self{path : path.to_owned(), handle : None};
+    }
+}
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sprite{
+    pub texture : String,
+    pub size : Vec2,
+    pub color : Vec4,
+    #[serde(skip)]
+    pub handle : Option<Handle<Texture>>,
+}
+impl Default for Sprite{
+    fn default() -> Self {
+        return Self{
+            texture : String::new(),
+            size : Vec2::ONE,
+            color : Vec4::ONE,
+            handle : None,
+        }
+    }
+}
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Projection{
+    ///The vertical field of view in radians.
+    Perspective{fov_y : f32, near : f32, far : f32},
+    ///The visible height in world units.
+    Orthographic{height : f32, near : f32, far : f32},
+}
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Camera{
+    pub projection : Projection,
+    pub clear_color : Vec4,
+    ///The active camera with the highest priority is the one drawn.
+    pub priority : i32,
+    pub active : bool,
+}
+impl Default for Camera{
+    fn default() -> Self {
+        return Self{
+            projection : Projection::Perspective{fov_y : std::f32::consts::FRAC_PI_3, near : 0.1, far : 1000.0},
+            clear_color : Vec4::new(0.0, 0.0, 0.0, 1.0),
+            priority : 0,
+            active : true,
+        }
+    }
+}
+impl Camera{
+    pub fn projection_matrix(&self, aspect : f32) -> Mat4{
+        return match self.projection{
+            Projection::Perspective{fov_y, near, far} => {Mat4::perspective_rh(fov_y, aspect, near, far)}
+            Projection::Orthographic{height, near, far} => {
+                let (half_width, half_height) = (height * aspect / 2.0, height / 2.0);
+                Mat4::orthographic_rh(-half_width, half_width, -half_height, half_height, near, far)
+            }
+        }
+    }
+}
+
+///Loads the assets of meshes and sprites without a handle, or whose path no longer matches their handle.
+pub fn resolve_assets(world : &World, assets : &AssetServer){
+    if world.is_registered::<Mesh>(){
+        for (_, mesh) in world.write::<Mesh>().iter_mut(){
+            if mesh.handle.as_ref().and_then(|handle| assets.path(handle)).as_ref() != Some(&mesh.path){
+                mesh.handle = Some(assets.load(&mesh.path));
+            }
+        }
+    }
+    if world.is_registered::<Sprite>(){
+        for (_, sprite) in world.write::<Sprite>().iter_mut(){
+            if sprite.texture.is_empty(){sprite.handle = None; continue;}
+            if sprite.handle.as_ref().and_then(|handle| assets.path(handle)).as_ref() != Some(&sprite.texture){
+                sprite.handle = Some(assets.load(&sprite.texture));
+            }
+        }
+    }
+}
+
+///Collects the renderable components positioned by their global transforms, the aspect ratio is the one of the target.
+pub fn extract(world : &World, aspect : f32) -> RenderScene{
+    let mut scene = RenderScene::default();
+    if !world.is_registered::<GlobalTransform>(){return scene}
+    let transforms = world.read::<GlobalTransform>();
+    if world.is_registered::<Camera>(){
+        let cameras = world.read::<Camera>();
+        scene.camera = join(&cameras, &transforms).filter(|(_, camera, _)| camera.active).max_by_key(|(_, camera, _)| camera.priority).map(|(_, camera, transform)| CameraView{
+            view : transform.0.inverse().to_cols_array_2d(),
+            projection : camera.projection_matrix(aspect).to_cols_array_2d(),
+            clear_color : camera.clear_color.to_array(),
+        });
+    }
+    if world.is_registered::<Mesh>(){
+        let meshes = world.read::<Mesh>();
+        scene.meshes = join(&meshes, &transforms).filter_map(|(_, mesh, transform)| Some(MeshInstance{
+            mesh : mesh.handle.as_ref()?.id(),
+            transform : transform.0.to_cols_array_2d(),
+        })).collect();
+    }
+    if world.is_registered::<Sprite>(){
+        let sprites = world.read::<Sprite>();
+        scene.sprites = join(&sprites, &transforms).filter_map(|(_, sprite, transform)| Some(SpriteInstance{
+            texture : sprite.handle.as_ref()?.id(),
+            size : sprite.size.to_array(),
+            color : sprite.color.to_array(),
+            transform : transform.0.to_cols_array_2d(),
+        })).collect();
+    }
+    return scene;
+}
@@ -0,0 +1,79 @@
+use std::any::TypeId;
+use crate::scene::World;
+use crate::scene::component::{AnyStorage, Component, Storage};
+
+type Access = (TypeId, fn() -> Box<dyn AnyStorage>);
+
+///A function over the world together with the component types it reads and writes.
+pub struct System{
+    name : String,
+    reads : Vec<Access>,
+    writes : Vec<Access>,
+    run : Box<dyn FnMut(&World) + Send>,
+}
+impl System{
+    pub fn new<F : FnMut(&World) + Send + 'static>(name : &str, run : F) -> Self{
+        return Self{
+            name : name.to_owned(),
+            reads : vec![],
+            writes : vec![],
+            run : Box::new(run),
+        }
+    }
+    pub fn reads<T : Component>(mut self) -> Self{
+        self.reads.push((TypeId::of::<T>(), || Box::<Storage<T>>::default()));
+        return self;
+    }
+    pub fn writes<T : Component>(mut self) -> Self{
+        self.writes.push((TypeId::of::<T>(), || Box::<Storage<T>>::default()));
+        return self;
+    }
+    pub fn name(&self) -> &str{
+        return &self.name;
+    }
+    fn conflicts(&self, other : &System) -> bool{
+        let writes = |system : &System, id : TypeId| system.writes.iter().any(|(write, _)| *write == id);
+        return self.writes.iter().chain(self.reads.iter()).any(|(id, _)| writes(other, *id))
+            || other.reads.iter().any(|(id, _)| writes(self, *id));
+    }
+}
+
+///Runs systems on the rayon pool, systems that do not write what another one accesses run at the same time.
+///Systems run in the order they were added whenever they conflict.
+#[derive(Default)]
+pub struct Schedule{
+    systems : Vec<System>,
+}
+impl Schedule{
+    pub fn new() -> Self{
+        return Self::default();
+    }
+    pub fn add_system(&mut self, system : System){
+        self.systems.push(system);
+    }
+    pub fn remove_system(&mut self, name : &str){
+        self.systems.retain(|system| system.name != name);
+    }
+    pub fn run(&mut self, world : &mut World){
+        for system in &self.systems{
+            for (id, create) in system.reads.iter().chain(system.writes.iter()){world.register_id(*id, *create)}
+        }
+        let shared : &World = world;
+        let mut remaining : &mut [System] = &mut self.systems;
+        while !remaining.is_empty(){
+            //A batch ends at the first system that conflicts with one already in it.
+            let mut length = 1;
+            while length < remaining.len() && !remaining[..length].iter().any(|system| system.conflicts(&remaining[length])){length += 1}
+            let (batch, rest) = remaining.split_at_mut(length);
+            if let [system] = batch{
+                (system.run)(shared);
+            } else {
+                rayon::scope(|scope| {
+                    for system in batch.iter_mut(){scope.spawn(move |_| (system.run)(shared))}
+                });
+            }
+            remaining = rest;
+        }
+        world.flush();
+    }
+}
@@ -0,0 +1,189 @@
+use glam::{Mat4, Quat, Vec3};
+use rayon::prelude::*;
+use serde_derive::{Deserialize, Serialize};
+use crate::scene::{Entity, World};
+use crate::scene::component::Storage;
+use crate::scene::system::System;
+
+///The transform relative to the parent, or to the world for entities without one.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Transform{
+    pub translation : Vec3,
+    pub rotation : Quat,
+    pub scale : Vec3,
+}
+impl Default for Transform{
+    fn default() -> Self {
+        return Self{
+            translation : Vec3::ZERO,
+            rotation : Quat::IDENTITY,
+            scale : Vec3::ONE,
+        }
+    }
+}
+impl Transform{
+    pub fn from_translation(translation : Vec3) -> Self{
+        return Self{translation, ..Default::default()};
+    }
+    pub fn matrix(&self) -> Mat4{
+        return Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation);
+    }
+}
+///The world space matrix, written by the propagation system.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct GlobalTransform(pub Mat4);
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Parent(pub Entity);
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Children(pub Vec<Entity>);
+
+impl World{
+    ///Attaches the child to the parent, refusing to create a cycle.
+    pub fn set_parent(&mut self, child : Entity, parent : Entity) -> bool{
+        if !self.is_alive(child) || !self.is_alive(parent) || child == parent || self.ancestors(parent).contains(&child){return false}
+        self.remove_parent(child);
+        self.insert(child, Parent(parent));
+        self.register::<Children>();
+        let mut children = self.write::<Children>();
+        match children.get_mut(parent){
+            Some(children) => {children.0.push(child)}
+            None => {children.insert(parent, Children(vec![child]));}
+        }
+        return true;
+    }
+    pub fn remove_parent(&mut self, child : Entity){
+        if let Some(Parent(parent)) = self.remove::<Parent>(child){
+            if let Some(children) = self.write::<Children>().get_mut(parent){children.0.retain(|entity| *entity != child)}
+        }
+    }
+    pub fn parent(&self, entity : Entity) -> Option<Entity>{
+        return self.get::<Parent>(entity).map(|parent| parent.0);
+    }
+    pub fn children(&self, entity : Entity) -> Vec<Entity>{
+        return self.get::<Children>(entity).map(|children| children.0).unwrap_or_default();
+    }
+    ///Despawns the entity together with all of its descendants.
+    pub fn despawn_recursive(&mut self, entity : Entity){
+        for child in self.children(entity){self.despawn_recursive(child)}
+        self.despawn(entity);
+    }
+    fn ancestors(&self, entity : Entity) -> Vec<Entity>{
+        let mut ancestors = vec![];
+        let mut current = self.parent(entity);
+        while let Some(parent) = current{
+            ancestors.push(parent);
+            current = self.parent(parent);
+        }
+        return ancestors;
+    }
+    ///Unlinks the entity from its parent and its children, used before it is despawned.
+    pub(crate) fn detach(&mut self, entity : Entity){
+        self.remove_parent(entity);
+        if let Some(Children(children)) = self.remove::<Children>(entity){
+            let mut parents = self.write::<Parent>();
+            for child in children{parents.remove(child);}
+        }
+    }
+}
+
+///Recomputes the global transforms from the roots down, every root's subtree is handled as its own rayon task.
+pub fn propagate_transforms(world : &World){
+    let transforms = world.read::<Transform>();
+    let parents = world.read::<Parent>();
+    let children = world.read::<Children>();
+    let roots : Vec<(Entity, Mat4)> = transforms.iter().filter(|(entity, _)| !parents.contains(*entity)).map(|(entity, transform)| (entity, transform.matrix())).collect();
+    let globals : Vec<(Entity, Mat4)> = roots.into_par_iter().flat_map_iter(|root| {
+        let mut globals = vec![];
+        let mut stack = vec![root];
+        while let Some((entity, matrix)) = stack.pop(){
+            globals.push((entity, matrix));
+            for child in children.get(entity).map(|children| children.0.as_slice()).unwrap_or_default(){
+                if let Some(transform) = transforms.get(*child){stack.push((*child, matrix * transform.matrix()))}
+            }
+        }
+        globals
+    }).collect();
+    let mut global_transforms = world.write::<GlobalTransform>();
+    for (entity, matrix) in globals{
+        global_transforms.insert(entity, GlobalTransform(matrix));
+    }
+    remove_stale(&transforms, &mut global_transforms);
+}
+fn remove_stale(transforms : &Storage<Transform>, global_transforms : &mut Storage<GlobalTransform>){
+    let stale : Vec<Entity> = global_transforms.entities().iter().copied().filter(|entity| !transforms.contains(*entity)).collect();
+    for entity in stale{global_transforms.remove(entity);}
+}
+pub fn propagation_system() -> System{
+    return System::new("propagate_transforms", propagate_transforms)
+        .reads::<Transform>()
+        .reads::<Parent>()
+        .reads::<Children>()
+        .writes::<GlobalTransform>();
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    ///A world with the components the engine registers for the propagation.
+    fn registered_world() -> World{
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<Parent>();
+        world.register::<Children>();
+        world.register::<GlobalTransform>();
+        return world;
+    }
+    fn spawn(world : &mut World, translation : Vec3) -> Entity{
+        let entity = world.spawn();
+        world.insert(entity, Transform::from_translation(translation));
+        return entity;
+    }
+    fn global_translation(world : &World, entity : Entity) -> Option<Vec3>{
+        return world.get::<GlobalTransform>(entity).map(|global| global.0.w_axis.truncate());
+    }
+
+    #[test]
+    fn children_are_placed_relative_to_their_parents(){
+        let mut world = registered_world();
+        let root = spawn(&mut world, Vec3::new(1.0, 0.0, 0.0));
+        let child = spawn(&mut world, Vec3::new(0.0, 2.0, 0.0));
+        let grandchild = spawn(&mut world, Vec3::new(0.0, 0.0, 3.0));
+        let other = spawn(&mut world, Vec3::new(5.0, 0.0, 0.0));
+        assert!(world.set_parent(child, root) && world.set_parent(grandchild, child));
+        world.insert(root, Transform{scale : Vec3::splat(2.0), ..Transform::from_translation(Vec3::new(1.0, 0.0, 0.0))});
+        propagate_transforms(&world);
+        assert_eq!(global_translation(&world, root), Some(Vec3::new(1.0, 0.0, 0.0)));
+        assert_eq!(global_translation(&world, child), Some(Vec3::new(1.0, 4.0, 0.0)));
+        assert_eq!(global_translation(&world, grandchild), Some(Vec3::new(1.0, 4.0, 6.0)));
+        assert_eq!(global_translation(&world, other), Some(Vec3::new(5.0, 0.0, 0.0)));
+    }
+    #[test]
+    fn reparenting_and_removed_transforms_are_picked_up(){
+        let mut world = registered_world();
+        let first = spawn(&mut world, Vec3::new(1.0, 0.0, 0.0));
+        let second = spawn(&mut world, Vec3::new(0.0, 1.0, 0.0));
+        let child = spawn(&mut world, Vec3::new(0.0, 0.0, 1.0));
+        world.set_parent(child, first);
+        propagate_transforms(&world);
+        assert_eq!(global_translation(&world, child), Some(Vec3::new(1.0, 0.0, 1.0)));
+        world.set_parent(child, second);
+        propagate_transforms(&world);
+        assert_eq!(global_translation(&world, child), Some(Vec3::new(0.0, 1.0, 1.0)));
+        assert_eq!(world.children(first), vec![]);
+        world.remove::<Transform>(second);
+        propagate_transforms(&world);
+        assert_eq!(global_translation(&world, second), None);
+    }
+    #[test]
+    fn cycles_are_refused(){
+        let mut world = registered_world();
+        let parent = spawn(&mut world, Vec3::ZERO);
+        let child = spawn(&mut world, Vec3::ZERO);
+        assert!(world.set_parent(child, parent));
+        assert!(!world.set_parent(parent, child));
+        assert!(!world.set_parent(parent, parent));
+        assert_eq!(world.parent(parent), None);
+    }
+}
@@ -0,0 +1,83 @@
+use serde_derive::{Deserialize, Serialize};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::error::OsError;
+use winit::event_loop::EventLoop;
+use winit::monitor::MonitorHandle;
+use winit::window::{Fullscreen, Window, WindowBuilder};
+use omage_util::config::Versioned;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum FullscreenMode{
+    Windowed,
+    Borderless,
+    Exclusive,
+}
+
+///The window settings stored in window.toml, the geometry is updated from the window on exit.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig{
+    pub width : u32,
+    pub height : u32,
+    ///The outer position in physical pixels, left to the platform when unset.
+    pub position : Option<(i32, i32)>,
+    pub fullscreen : FullscreenMode,
+    ///The index of the monitor used for fullscreen, the primary monitor when unset or unavailable.
+    pub monitor : Option<usize>,
+    pub resizable : bool,
+    pub decorations : bool,
+    pub always_on_top : bool,
+    pub cursor_grab : bool,
+}
+impl Versioned for WindowConfig{}
+impl Default for WindowConfig{
+    fn default() -> Self {
+        return Self{
+            width : 1280,
+            height : 720,
+            position : None,
+            fullscreen : FullscreenMode::Windowed,
+            monitor : None,
+            resizable : true,
+            decorations : true,
+            always_on_top : false,
+            cursor_grab : false,
+        }
+    }
+}
+impl WindowConfig{
+    pub fn build(&self, event_loop : &EventLoop<()>) -> Result<Window, OsError>{
+        let fullscreen = self.fullscreen(event_loop.available_monitors().collect(), event_loop.primary_monitor());
+        let mut builder = WindowBuilder::new()
+            .with_inner_size(PhysicalSize::new(self.width, self.height))
+            .with_fullscreen(fullscreen)
+            .with_resizable(self.resizable)
+            .with_decorations(self.decorations)
+            .with_always_on_top(self.always_on_top);
+        if let Some((x, y)) = self.position{builder = builder.with_position(PhysicalPosition::new(x, y))}
+        return builder.build(event_loop);
+    }
+    ///Resolves the fullscreen mode on the configured monitor, exclusive mode picks the highest refresh rate at the configured size.
+    pub fn fullscreen(&self, monitors : Vec<MonitorHandle>, primary : Option<MonitorHandle>) -> Option<Fullscreen>{
+        let monitor = self.monitor.and_then(|index| monitors.get(index).cloned()).or(primary).or_else(|| monitors.first().cloned());
+        return match self.fullscreen{
+            FullscreenMode::Windowed => {None}
+            FullscreenMode::Borderless => {Some(Fullscreen::Borderless(monitor))}
+            FullscreenMode::Exclusive => {
+                let mode = monitor?.video_modes().max_by_key(|mode| {
+                    let size = mode.size();
+                    (size.width == self.width && size.height == self.height, mode.refresh_rate(), size.width * size.height)
+                });
+                mode.map(Fullscreen::Exclusive)
+            }
+        }
+    }
+    ///Stores the current geometry of a windowed window so it reopens where it was left.
+    pub fn update_geometry(&mut self, window : &Window){
+        if window.fullscreen().is_some(){return;}
+        let size = window.inner_size();
+        self.width = size.width;
+        self.height = size.height;
+        if let Ok(position) = window.outer_position(){self.position = Some((position.x, position.y))}
+    }
+}
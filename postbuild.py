@@ -2,6 +2,7 @@ import shader
 import pathlib
 import shutil;
 import os;
+import subprocess
 
 if __name__ == '__main__':
     current_dir = pathlib.Path(__file__).parent.absolute()
@@ -11,8 +12,6 @@ if __name__ == '__main__':
         os.mkdir(current_dir.joinpath("./target/release/"))
     if not os.path.isdir(current_dir.joinpath("./target/debug/")):
         os.mkdir(current_dir.joinpath("./target/debug/"))
-    if not os.path.isdir(current_dir.joinpath("./target/release/assets")):
-        os.mkdir(current_dir.joinpath("./target/release/assets"))
     if not os.path.isdir(current_dir.joinpath("./target/debug/assets")):
         os.mkdir(current_dir.joinpath("./target/debug/assets"))
     shader.compile_shaders()
@@ -21,4 +20,5 @@ if __name__ == '__main__':
     for root, _, files in os.walk(assets_dir):
         for file in files:
             shutil.copy(current_dir.joinpath("./assets/"+file), current_dir.joinpath("./target/debug/assets/" + file))
-            shutil.copy(current_dir.joinpath("./assets/"+file), current_dir.joinpath("./target/release/assets/" + file))
+    # Release builds ship the assets packed, debug builds keep loose files so they can be hot reloaded.
+    subprocess.run(["cargo", "run", "--release", "-p", "omage-pack", "--", str(assets_dir), str(current_dir.joinpath("./target/release/assets.pak"))], cwd=current_dir, check=True)